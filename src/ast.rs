@@ -0,0 +1,413 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+}
+
+/// A test applied to each die on its own, like the `>= 5` in
+/// `explode on >= 5`. `op` is always a comparison.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Condition {
+    pub op: BinaryOp,
+    pub value: Box<Expr>,
+}
+
+impl BinaryOp {
+    /// Whether this is one of `<`, `<=`, `>`, `>=`, `==`, or `!=`.
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge | BinaryOp::Eq | BinaryOp::Ne
+        )
+    }
+
+    /// Applies this operator to `left` and `right`, giving `None` if that
+    /// divides by zero or the result doesn't fit in an `i32`. Comparisons give
+    /// 1 when they hold and 0 otherwise, and division truncates toward zero.
+    pub fn apply(self, left: i32, right: i32) -> Option<i32> {
+        match self {
+            BinaryOp::Add => left.checked_add(right),
+            BinaryOp::Sub => left.checked_sub(right),
+            BinaryOp::Mul => left.checked_mul(right),
+            BinaryOp::Div => left.checked_div(right),
+            _ => Some(i32::from(self.holds(left, right))),
+        }
+    }
+
+    /// Whether `left` and `right` compare this way. Always false for the
+    /// arithmetic operators.
+    pub fn holds(self, left: i32, right: i32) -> bool {
+        match self {
+            BinaryOp::Lt => left < right,
+            BinaryOp::Le => left <= right,
+            BinaryOp::Gt => left > right,
+            BinaryOp::Ge => left >= right,
+            BinaryOp::Eq => left == right,
+            BinaryOp::Ne => left != right,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => false,
+        }
+    }
+}
+
+/// What [`Expr::Reroll`] does with a die that meets its condition.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RerollStrategy {
+    /// Roll it once more and keep the new result, whatever it is.
+    #[default]
+    Once,
+    /// Keep rolling it until it no longer meets the condition.
+    Until,
+}
+
+/// Which dice of a [`Expr::DicePool`] count toward its total.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PoolModifier {
+    /// `kh`: only the highest this many dice.
+    KeepHighest(Box<Expr>),
+    /// `kl`: only the lowest this many dice.
+    KeepLowest(Box<Expr>),
+    /// `dh`: all but the highest this many dice.
+    DropHighest(Box<Expr>),
+    /// `dl`: all but the lowest this many dice.
+    DropLowest(Box<Expr>),
+    /// Every die.
+    None,
+}
+
+/// Which dice of an [`Expr::KeepDrop`] count toward its total.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeepDropKind {
+    KeepHighest,
+    KeepLowest,
+    DropHighest,
+    DropLowest,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Int(i32),
+    /// `count` dice with `sides` sides each, as in `2d6`.
+    Dice {
+        count: Box<Expr>,
+        sides: Box<Expr>,
+    },
+    /// `count` dice with `sides` sides each, totalled as `modifier` says, as
+    /// in `4d6kh3`.
+    DicePool {
+        count: Box<Expr>,
+        sides: Box<Expr>,
+        modifier: PoolModifier,
+    },
+    BinaryOp {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    UnaryOp {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    /// A roll against a target, like `d20 >= 15`, which is 1 when it succeeds
+    /// and 0 when it fails. `op` is always a comparison.
+    ///
+    /// When `lhs` is a pool of dice, like `5d10 >= 8`, each die is compared on
+    /// its own and the result is the number of successes. Grouping the pool,
+    /// as in `(2d6) >= 7`, compares its total instead.
+    Comparison {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// The sum of the highest `count` dice in `pool`, as in `4d6k3`.
+    Keep {
+        pool: Box<Expr>,
+        count: Box<Expr>,
+    },
+    /// The sum of `pool` without its lowest `count` dice, as in `4d6 drop 1`.
+    Drop {
+        pool: Box<Expr>,
+        count: Box<Expr>,
+    },
+    /// Keeps or drops the highest or lowest `count` dice of any pool, as in
+    /// `(4d6!) keep high 3` or `(2d6 reroll once == 1) dl1`. A modifier right
+    /// after plain dice, like `4d6kh3`, is a [`Expr::DicePool`] instead.
+    KeepDrop {
+        inner: Box<Expr>,
+        kind: KeepDropKind,
+        count: Box<Expr>,
+    },
+    /// Dice that are rolled again and added whenever they meet `threshold`,
+    /// as in `d6!` or `2d6 explode on >= 5`, chaining at most `depth` times.
+    /// Without a threshold, dice explode on their highest face.
+    Explode {
+        inner: Box<Expr>,
+        threshold: Option<Condition>,
+        depth: u32,
+    },
+    /// Dice that are rolled again when they meet `condition`, as in
+    /// `3d6 reroll once <= 2`.
+    Reroll {
+        inner: Box<Expr>,
+        condition: Condition,
+        strategy: RerollStrategy,
+    },
+    /// The highest of its arguments, as in `max(d6, d6)`.
+    Max(Vec<Expr>),
+    /// The lowest of its arguments, as in `min(d20, 10)`.
+    Min(Vec<Expr>),
+    /// A parenthesized expression, kept so the tree mirrors the source.
+    Group(Box<Expr>),
+}
+
+impl Expr {
+    /// The expressions directly inside this one, in source order, including
+    /// the values of any conditions.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Int(_) => Vec::new(),
+            Expr::Dice { count, sides } => vec![count, sides],
+            Expr::DicePool {
+                count,
+                sides,
+                modifier,
+            } => match modifier {
+                PoolModifier::KeepHighest(n)
+                | PoolModifier::KeepLowest(n)
+                | PoolModifier::DropHighest(n)
+                | PoolModifier::DropLowest(n) => vec![count, sides, n],
+                PoolModifier::None => vec![count, sides],
+            },
+            Expr::BinaryOp { left, right, .. } => vec![left, right],
+            Expr::UnaryOp { operand, .. } => vec![operand],
+            Expr::Comparison { lhs, rhs, .. } => vec![lhs, rhs],
+            Expr::Keep { pool, count } | Expr::Drop { pool, count } => vec![pool, count],
+            Expr::KeepDrop { inner, count, .. } => vec![inner, count],
+            Expr::Explode {
+                inner, threshold, ..
+            } => match threshold {
+                Some(threshold) => vec![inner, &threshold.value],
+                None => vec![inner],
+            },
+            Expr::Reroll {
+                inner, condition, ..
+            } => vec![inner, &condition.value],
+            Expr::Max(args) | Expr::Min(args) => args.iter().collect(),
+            Expr::Group(inner) => vec![inner],
+        }
+    }
+
+    /// Whether this rolls dice that a comparison counts one by one, rather
+    /// than comparing their total. See [`Expr::Comparison`].
+    pub(crate) fn counts_successes(&self) -> bool {
+        matches!(
+            self,
+            Expr::Dice { .. } | Expr::Explode { .. } | Expr::Reroll { .. }
+        )
+    }
+
+    /// Whether an [`Expr::Explode`] appears anywhere in this expression.
+    pub(crate) fn contains_explode(&self) -> bool {
+        matches!(self, Expr::Explode { .. })
+            || self.children().into_iter().any(Expr::contains_explode)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+        };
+
+        write!(f, "{symbol}")
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.op, self.value)
+    }
+}
+
+/// Writes source text that parses back into the same expression.
+///
+/// Parentheses only come from [`Expr::Group`], so a tree built by hand without
+/// groups where precedence needs them won't survive the trip.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Int(n) => write!(f, "{n}"),
+            Expr::Dice { count, sides } if **count == Expr::Int(1) => write!(f, "d{sides}"),
+            Expr::Dice { count, sides } => write!(f, "{count}d{sides}"),
+            Expr::DicePool {
+                count,
+                sides,
+                modifier,
+            } => match modifier {
+                PoolModifier::KeepHighest(n) => write!(f, "{count}d{sides}kh{n}"),
+                PoolModifier::KeepLowest(n) => write!(f, "{count}d{sides}kl{n}"),
+                PoolModifier::DropHighest(n) => write!(f, "{count}d{sides}dh{n}"),
+                PoolModifier::DropLowest(n) => write!(f, "{count}d{sides}dl{n}"),
+                PoolModifier::None => write!(f, "{count}d{sides}"),
+            },
+            Expr::BinaryOp { left, op, right } => write!(f, "{left} {op} {right}"),
+            Expr::UnaryOp {
+                op: UnaryOp::Neg,
+                operand,
+            } => write!(f, "-{operand}"),
+            Expr::Comparison { op, lhs, rhs } => write!(f, "{lhs} {op} {rhs}"),
+            Expr::Keep { pool, count } => write!(f, "{pool}k{count}"),
+            Expr::Drop { pool, count } => write!(f, "{pool} drop {count}"),
+            // The long spelling, since `kh` right after plain dice would
+            // make a `DicePool` instead.
+            Expr::KeepDrop { inner, kind, count } => match kind {
+                KeepDropKind::KeepHighest => write!(f, "{inner} keep high {count}"),
+                KeepDropKind::KeepLowest => write!(f, "{inner} keep low {count}"),
+                KeepDropKind::DropHighest => write!(f, "{inner} drop high {count}"),
+                KeepDropKind::DropLowest => write!(f, "{inner} drop low {count}"),
+            },
+            Expr::Explode {
+                inner,
+                threshold: None,
+                ..
+            } => write!(f, "{inner}!"),
+            Expr::Explode {
+                inner,
+                threshold: Some(threshold),
+                ..
+            } => write!(f, "{inner} explode on {threshold}"),
+            Expr::Reroll {
+                inner,
+                condition,
+                strategy: RerollStrategy::Once,
+            } => write!(f, "{inner} reroll once {condition}"),
+            Expr::Reroll {
+                inner,
+                condition,
+                strategy: RerollStrategy::Until,
+            } => write!(f, "{inner} reroll until {condition}"),
+            Expr::Max(args) => write!(f, "max({})", comma_separated(args)),
+            Expr::Min(args) => write!(f, "min({})", comma_separated(args)),
+            Expr::Group(inner) => write!(f, "({inner})"),
+        }
+    }
+}
+
+fn comma_separated(exprs: &[Expr]) -> String {
+    let exprs: Vec<String> = exprs.iter().map(Expr::to_string).collect();
+    exprs.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, tokenize_strict};
+
+    fn int(n: i32) -> Box<Expr> {
+        Box::new(Expr::Int(n))
+    }
+
+    #[test]
+    fn test_display() {
+        let dice = Expr::Dice {
+            count: int(2),
+            sides: int(6),
+        };
+
+        assert_eq!("6", Expr::Int(6).to_string());
+        assert_eq!("2d6", dice.to_string());
+        assert_eq!(
+            "d20",
+            Expr::Dice {
+                count: int(1),
+                sides: int(20),
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "2 + 2d6",
+            Expr::BinaryOp {
+                left: int(2),
+                op: BinaryOp::Add,
+                right: Box::new(dice),
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let inputs = [
+            "1 + 2 * 3",
+            "(1 + 2) * 3",
+            "8 - 4 / 2 - 1",
+            "3 - -2",
+            "-2d6",
+            "d20 + 5 >= 15",
+            "d20 != 1",
+            "4d6k3",
+            "4d6 keep 3",
+            "4d6 drop 1",
+            "4d6kh3 + 2d20dl1",
+            "4d6kl(1 + 2)",
+            "(4d6)kh3",
+            "4d6 keep high 3",
+            "(2d6!) drop low 1",
+            "d6!",
+            "2d6 explode on >= 5",
+            "3d6 reroll once <= 2",
+            "3d6 rr < 3",
+            "3d6 reroll until > 1",
+            "(d4)d(2 + 2)",
+            "5d10 >= 8",
+            "2d6d4",
+            "max(d6, d6) + min(d20, 10, 2d4)",
+            "1d6 - d6k1",
+        ];
+
+        for input in inputs {
+            let expr = parse(&tokenize_strict(input).unwrap()).unwrap();
+            let text = expr.to_string();
+            let reparsed = parse(&tokenize_strict(&text).unwrap()).unwrap();
+
+            assert_eq!(expr, reparsed, "{input} was displayed as {text}");
+        }
+    }
+
+    #[test]
+    fn test_children() {
+        let expr = parse(&tokenize_strict("2d6 explode on >= 5 + max(1, 2, 3)").unwrap()).unwrap();
+        let children: Vec<String> = expr
+            .children()
+            .iter()
+            .map(|child| child.to_string())
+            .collect();
+
+        assert_eq!(vec!["2d6 explode on >= 5", "max(1, 2, 3)"], children);
+        assert_eq!(3, expr.children()[1].children().len());
+        assert!(Expr::Int(1).children().is_empty());
+    }
+}
@@ -0,0 +1,628 @@
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    ops::{Add, Neg},
+};
+
+/// A discrete probability distribution over integer outcomes.
+///
+/// Only outcomes that can happen are stored, and their probabilities always
+/// sum to 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+    pub(crate) outcomes: BTreeMap<i32, f64>,
+}
+
+/// The chances of one roll coming out above, level with, or below another, as
+/// found by [`Distribution::compare`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComparisonResult {
+    pub prob_greater: f64,
+    pub prob_equal: f64,
+    pub prob_less: f64,
+}
+
+impl Distribution {
+    /// The distribution of rolling no dice at all, which always totals 0.
+    pub fn new() -> Self {
+        Self::constant(0)
+    }
+
+    /// A distribution that is always `n`.
+    pub fn constant(n: i32) -> Self {
+        Self {
+            outcomes: BTreeMap::from([(n, 1.0)]),
+        }
+    }
+
+    /// Every outcome from `min` to `max` inclusive, each equally likely, like
+    /// a fair die.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`.
+    pub fn uniform(min: i32, max: i32) -> Self {
+        assert!(min <= max, "empty range {min}..={max}");
+
+        let p = 1.0 / (f64::from(max) - f64::from(min) + 1.0);
+
+        Self {
+            outcomes: (min..=max).map(|n| (n, p)).collect(),
+        }
+    }
+
+    /// The probability of `outcome`, which is 0 if it can't happen.
+    pub fn probability(&self, outcome: i32) -> f64 {
+        self.outcomes.get(&outcome).copied().unwrap_or(0.0)
+    }
+
+    /// The probability of rolling exactly `outcome`. The same as
+    /// [`probability`](Self::probability), to read alongside
+    /// [`at_least`](Self::at_least) and [`at_most`](Self::at_most).
+    pub fn exactly(&self, outcome: i32) -> f64 {
+        self.probability(outcome)
+    }
+
+    /// The probability of an outcome of `threshold` or more.
+    pub fn at_least(&self, threshold: i32) -> f64 {
+        if threshold <= self.min() {
+            return 1.0;
+        }
+
+        self.outcomes.range(threshold..).map(|(_, &p)| p).sum()
+    }
+
+    /// The probability of an outcome of `threshold` or less.
+    pub fn at_most(&self, threshold: i32) -> f64 {
+        if threshold >= self.max() {
+            return 1.0;
+        }
+
+        self.outcomes.range(..=threshold).map(|(_, &p)| p).sum()
+    }
+
+    /// Each outcome from lowest to highest with the probability of rolling it
+    /// or less.
+    pub fn cumulative(&self) -> Vec<(i32, f64)> {
+        let mut total = 0.0;
+
+        self.outcomes()
+            .map(|(n, p)| {
+                total += p;
+                (n, total)
+            })
+            .collect()
+    }
+
+    /// The chances a roll of this beats, ties, or loses to an independent
+    /// roll of `other`, like `2d6` against `d12`.
+    pub fn compare(&self, other: &Self) -> ComparisonResult {
+        let mut result = ComparisonResult {
+            prob_greater: 0.0,
+            prob_equal: 0.0,
+            prob_less: 0.0,
+        };
+
+        for (a, p) in self.outcomes() {
+            for (b, q) in other.outcomes() {
+                match a.cmp(&b) {
+                    Ordering::Greater => result.prob_greater += p * q,
+                    Ordering::Equal => result.prob_equal += p * q,
+                    Ordering::Less => result.prob_less += p * q,
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Every possible outcome with its probability, from lowest to highest.
+    pub fn outcomes(&self) -> impl Iterator<Item = (i32, f64)> + '_ {
+        self.outcomes.iter().map(|(&n, &p)| (n, p))
+    }
+
+    /// The lowest possible outcome.
+    pub fn min(&self) -> i32 {
+        *self.outcomes.keys().next().unwrap()
+    }
+
+    /// The highest possible outcome.
+    pub fn max(&self) -> i32 {
+        *self.outcomes.keys().next_back().unwrap()
+    }
+
+    /// The probability-weighted average of the outcomes.
+    pub fn mean(&self) -> f64 {
+        self.outcomes().map(|(n, p)| f64::from(n) * p).sum()
+    }
+
+    /// How spread out the outcomes are, computed as E[(X − mean)²].
+    ///
+    /// The probabilities are divided by their total first, so rounding error
+    /// that built up while combining dice doesn't skew the result.
+    pub fn variance(&self) -> f64 {
+        let total: f64 = self.outcomes().map(|(_, p)| p).sum();
+        let mean = self.mean() / total;
+
+        self.outcomes()
+            .map(|(n, p)| (f64::from(n) - mean).powi(2) * p)
+            .sum::<f64>()
+            / total
+    }
+
+    /// The square root of the [variance](Self::variance).
+    pub fn standard_deviation(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// The middle outcome: the lowest with at least even odds of rolling it or
+    /// less, which is the 50th [percentile](Self::percentile).
+    ///
+    /// When the odds come out exactly even, as they do at 3 on a d6, the
+    /// median is halfway between that outcome and the next one up, making it
+    /// 3.5 for a d6.
+    pub fn median(&self) -> f64 {
+        let lower = self.percentile(0.5);
+
+        if (self.at_most(lower) - 0.5).abs() > 1e-9 {
+            return f64::from(lower);
+        }
+
+        match self.outcomes.range(lower + 1..).next() {
+            Some((&upper, _)) => (f64::from(lower) + f64::from(upper)) / 2.0,
+            None => f64::from(lower),
+        }
+    }
+
+    /// The lowest outcome `n` with a probability of at least `p` of rolling
+    /// `n` or less, so a d6 has a 50th percentile of 3 and a 90th of 6.
+    ///
+    /// Probabilities within a small tolerance of `p` count as meeting it, so
+    /// rounding doesn't push the result up an outcome.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` isn't between 0 and 1 inclusive.
+    pub fn percentile(&self, p: f64) -> i32 {
+        assert!((0.0..=1.0).contains(&p), "{p} isn't a probability");
+
+        self.cumulative()
+            .into_iter()
+            .find(|&(_, q)| q >= p - 1e-9)
+            .map(|(n, _)| n)
+            .unwrap_or_else(|| self.max())
+    }
+
+    /// Every outcome that's as likely as the likeliest, from lowest to highest.
+    ///
+    /// As with [`median`](Self::median), probabilities within a small
+    /// tolerance of each other count as tied.
+    pub fn mode(&self) -> Vec<i32> {
+        let highest = self.outcomes().map(|(_, p)| p).fold(0.0, f64::max);
+
+        self.outcomes()
+            .filter(|&(_, p)| p >= highest - 1e-9)
+            .map(|(n, _)| n)
+            .collect()
+    }
+
+    /// Renders the distribution as a JSON array of
+    /// `{"value": n, "probability": p}` objects, from lowest value to highest.
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self
+            .outcomes()
+            .map(|(n, p)| format!(r#"{{"value":{n},"probability":{p}}}"#))
+            .collect();
+
+        format!("[{}]", entries.join(","))
+    }
+
+    /// Renders one row per outcome, from lowest to highest, giving its
+    /// probability as a percentage and a bar of `#`s. The most likely outcome's
+    /// bar is `width` characters long and the rest are scaled to match.
+    pub fn histogram(&self, width: usize) -> String {
+        let most_likely = self.outcomes().map(|(_, p)| p).fold(0.0, f64::max);
+        let value_width = self
+            .outcomes()
+            .map(|(n, _)| n.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let mut rows = String::new();
+
+        for (n, p) in self.outcomes() {
+            let bar = (p / most_likely * width as f64).round() as usize;
+            let row = format!("{n:>value_width$} {:>6.2}% {}", p * 100.0, "#".repeat(bar));
+
+            rows.push_str(row.trim_end());
+            rows.push('\n');
+        }
+
+        rows
+    }
+
+    /// The distribution of the sum of independent draws from `self` and
+    /// `other`, such as the total of two dice rolled together.
+    pub fn convolve(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a + b)
+    }
+
+    /// The distribution of `f(a, b)`, where `a` and `b` are drawn
+    /// independently from `self` and `other`.
+    pub(crate) fn combine(&self, other: &Self, f: impl Fn(i32, i32) -> i32) -> Self {
+        let mut outcomes = BTreeMap::new();
+
+        for (a, p) in self.outcomes() {
+            for (b, q) in other.outcomes() {
+                *outcomes.entry(f(a, b)).or_insert(0.0) += p * q;
+            }
+        }
+
+        Self { outcomes }
+    }
+
+    /// Like [`combine`](Self::combine), but gives `None` if `f` does for any
+    /// pair of outcomes.
+    pub(crate) fn try_combine(
+        &self,
+        other: &Self,
+        f: impl Fn(i32, i32) -> Option<i32>,
+    ) -> Option<Self> {
+        let mut outcomes = BTreeMap::new();
+
+        for (a, p) in self.outcomes() {
+            for (b, q) in other.outcomes() {
+                *outcomes.entry(f(a, b)?).or_insert(0.0) += p * q;
+            }
+        }
+
+        Some(Self { outcomes })
+    }
+
+    /// The distribution of `f(a)`, where `a` is drawn from `self`.
+    pub(crate) fn map(&self, f: impl Fn(i32) -> i32) -> Self {
+        let mut outcomes = BTreeMap::new();
+
+        for (n, p) in self.outcomes() {
+            *outcomes.entry(f(n)).or_insert(0.0) += p;
+        }
+
+        Self { outcomes }
+    }
+
+    /// Like [`map`](Self::map), but gives `None` if `f` does for any outcome.
+    pub(crate) fn try_map(&self, f: impl Fn(i32) -> Option<i32>) -> Option<Self> {
+        let mut outcomes = BTreeMap::new();
+
+        for (n, p) in self.outcomes() {
+            *outcomes.entry(f(n)?).or_insert(0.0) += p;
+        }
+
+        Some(Self { outcomes })
+    }
+}
+
+impl Default for Distribution {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The sum of independent rolls, as with [`Distribution::convolve`], so
+/// `d6.clone() + d6` is 2d6.
+impl Add for Distribution {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        self.convolve(&rhs)
+    }
+}
+
+/// Adds a flat modifier to every outcome.
+impl Add<i32> for Distribution {
+    type Output = Self;
+
+    fn add(self, rhs: i32) -> Self {
+        self.map(|n| n + rhs)
+    }
+}
+
+/// Flips the sign of every outcome, keeping its probability.
+impl Neg for Distribution {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.map(|n| -n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_d6() -> Distribution {
+        let d6 = Distribution::uniform(1, 6);
+        d6.convolve(&d6)
+    }
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-12,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(Distribution::constant(0), Distribution::new());
+        assert_eq!(Distribution::new(), Distribution::default());
+    }
+
+    #[test]
+    fn test_uniform_d6() {
+        let d6 = Distribution::uniform(1, 6);
+        let outcomes: Vec<i32> = d6.outcomes().map(|(n, _)| n).collect();
+
+        assert_eq!(vec![1, 2, 3, 4, 5, 6], outcomes);
+
+        for face in 1..=6 {
+            assert_close(1.0 / 6.0, d6.probability(face));
+        }
+
+        assert_eq!(0.0, d6.probability(0));
+        assert_eq!(0.0, d6.probability(7));
+        assert_close(1.0, d6.outcomes().map(|(_, p)| p).sum());
+    }
+
+    #[test]
+    #[should_panic(expected = "empty range")]
+    fn test_uniform_empty_range() {
+        Distribution::uniform(6, 1);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = two_d6().to_json();
+        let entries: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(11, entries.len());
+        assert!(json.starts_with(r#"[{"value":2,"probability":0.02777"#));
+
+        let seven = &entries[5];
+        assert_eq!(7, seven["value"]);
+        assert_close(6.0 / 36.0, seven["probability"].as_f64().unwrap());
+
+        for (entry, (n, p)) in entries.iter().zip(two_d6().outcomes()) {
+            assert_eq!(i64::from(n), entry["value"].as_i64().unwrap());
+            assert_eq!(p, entry["probability"].as_f64().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = two_d6().histogram(20);
+        let rows: Vec<&str> = histogram.lines().collect();
+
+        assert_eq!(11, rows.len());
+        assert_eq!(" 2   2.78% ###", rows[0]);
+        assert_eq!(" 7  16.67% ####################", rows[5]);
+
+        let longest = rows.iter().max_by_key(|row| row.matches('#').count());
+        assert_eq!(Some(&rows[5]), longest);
+    }
+
+    #[test]
+    fn test_at_least_and_at_most() {
+        let d20 = Distribution::uniform(1, 20);
+
+        assert_eq!(1.0, d20.at_least(1));
+        assert_eq!(1.0, d20.at_least(-5));
+        assert_eq!(0.0, d20.at_least(21));
+        assert_eq!(1.0, d20.at_most(20));
+        assert_eq!(0.0, d20.at_most(0));
+        assert_close(0.3, d20.at_least(15));
+
+        let two_dice = two_d6();
+
+        assert_close(21.0 / 36.0, two_dice.at_least(7));
+        assert_close(21.0 / 36.0, two_dice.at_most(7));
+        assert_close(1.0, two_dice.at_least(8) + two_dice.at_most(7));
+    }
+
+    #[test]
+    fn test_d6_queries() {
+        let d6 = Distribution::uniform(1, 6);
+
+        assert_close(0.5, d6.at_least(4));
+        assert_close(0.5, d6.at_most(3));
+        assert_close(1.0 / 6.0, d6.exactly(1));
+        assert_eq!(0.0, d6.exactly(7));
+        assert_close(1.0 / 36.0, two_d6().exactly(12));
+    }
+
+    #[test]
+    fn test_cumulative() {
+        let cumulative = two_d6().cumulative();
+
+        assert_eq!(11, cumulative.len());
+        assert_eq!(2, cumulative[0].0);
+        assert_close(1.0 / 36.0, cumulative[0].1);
+        assert_close(21.0 / 36.0, cumulative[5].1);
+        assert_eq!(12, cumulative[10].0);
+        assert_close(1.0, cumulative[10].1);
+    }
+
+    #[test]
+    fn test_convolve() {
+        let d6 = Distribution::uniform(1, 6);
+        let sum = d6.convolve(&d6);
+
+        assert_eq!(11, sum.outcomes().count());
+        assert_eq!(2, sum.min());
+        assert_eq!(12, sum.max());
+
+        // Each of the 36 ordered pairs is equally likely, so a sum's chance is
+        // the number of pairs that add up to it over 36.
+        for (n, p) in sum.outcomes() {
+            let pairs = (1..=6).filter(|a| (1..=6).contains(&(n - a))).count();
+            assert_close(pairs as f64 / 36.0, p);
+        }
+
+        let (mode, _) = sum
+            .outcomes()
+            .max_by(|(_, p), (_, q)| p.total_cmp(q))
+            .unwrap();
+        assert_eq!(7, mode);
+    }
+
+    #[test]
+    fn test_convolve_with_constant_shifts() {
+        let shifted = Distribution::uniform(1, 4).convolve(&Distribution::constant(3));
+
+        assert_eq!(Distribution::uniform(4, 7), shifted);
+        assert_eq!(shifted, shifted.convolve(&Distribution::new()));
+    }
+
+    #[test]
+    fn test_statistics() {
+        let d6 = Distribution::uniform(1, 6);
+
+        assert_eq!(3.5, d6.mean());
+        assert_close(35.0 / 12.0, d6.variance());
+        assert_close((35.0f64 / 12.0).sqrt(), d6.standard_deviation());
+        assert_eq!(3.5, d6.median());
+
+        let d20_minus_two = Distribution::uniform(-1, 18);
+
+        assert_close(8.5, d20_minus_two.mean());
+        assert_close(399.0 / 12.0, d20_minus_two.variance());
+        assert_eq!(8.5, d20_minus_two.median());
+
+        let constant = Distribution::constant(7);
+
+        assert_eq!(7.0, constant.mean());
+        assert_eq!(0.0, constant.variance());
+        assert_eq!(7.0, constant.median());
+    }
+
+    #[test]
+    fn test_median_of_skewed_distribution() {
+        let three_d6 = two_d6().convolve(&Distribution::uniform(1, 6));
+
+        assert_close(10.5, three_d6.mean());
+        assert_close(35.0 / 4.0, three_d6.variance());
+        assert_eq!(10.5, three_d6.median());
+        assert_eq!(7.0, two_d6().median());
+        // Four in five rolls land on 0, so it's the median however high the
+        // rest go.
+        let mut outcomes = BTreeMap::from([(0, 0.8)]);
+        outcomes.insert(100, 0.2);
+        assert_eq!(0.0, Distribution { outcomes }.median());
+    }
+
+    #[test]
+    fn test_mode() {
+        assert_eq!(vec![1, 2, 3, 4, 5, 6], Distribution::uniform(1, 6).mode());
+        assert_eq!(vec![7], two_d6().mode());
+        assert_eq!(vec![5], Distribution::constant(5).mode());
+
+        let two_peaks = Distribution {
+            outcomes: BTreeMap::from([(1, 0.3), (2, 0.1), (3, 0.3), (4, 0.2), (5, 0.1)]),
+        };
+
+        assert_eq!(vec![1, 3], two_peaks.mode());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let d6 = Distribution::uniform(1, 6);
+
+        assert_eq!(3, d6.percentile(0.5));
+        assert_eq!(4, d6.percentile(0.51));
+        assert_eq!(6, d6.percentile(0.9));
+        assert_eq!(1, d6.percentile(0.0));
+        assert_eq!(6, d6.percentile(1.0));
+
+        let three_dice = Distribution::uniform(1, 6)
+            .convolve(&Distribution::uniform(1, 6))
+            .convolve(&Distribution::uniform(1, 6));
+
+        assert_eq!(3, three_dice.percentile(0.0));
+        assert_eq!(18, three_dice.percentile(1.0));
+        assert!(three_dice.at_most(three_dice.percentile(0.9)) >= 0.9);
+        assert!(three_dice.at_most(three_dice.percentile(0.9) - 1) < 0.9);
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't a probability")]
+    fn test_percentile_out_of_range() {
+        Distribution::uniform(1, 6).percentile(1.5);
+    }
+
+    #[test]
+    fn test_compare() {
+        let d6 = Distribution::uniform(1, 6);
+        let even = d6.compare(&d6);
+
+        assert_close(1.0 / 6.0, even.prob_equal);
+        assert_close(5.0 / 12.0, even.prob_greater);
+        assert_close(5.0 / 12.0, even.prob_less);
+
+        let d4 = Distribution::uniform(1, 4);
+        let d8 = Distribution::uniform(1, 8);
+        let lopsided = d4.compare(&d8);
+
+        assert_close(6.0 / 32.0, lopsided.prob_greater);
+        assert_close(4.0 / 32.0, lopsided.prob_equal);
+        assert_close(22.0 / 32.0, lopsided.prob_less);
+        assert_eq!(
+            (lopsided.prob_greater, lopsided.prob_less),
+            (d8.compare(&d4).prob_less, d8.compare(&d4).prob_greater)
+        );
+
+        for (a, b) in [(&d6, &d8), (&two_d6(), &Distribution::uniform(1, 12))] {
+            let result = a.compare(b);
+
+            assert_close(
+                1.0,
+                result.prob_greater + result.prob_equal + result.prob_less,
+            );
+        }
+    }
+
+    #[test]
+    fn test_median_on_even_odds() {
+        let bimodal = Distribution {
+            outcomes: BTreeMap::from([(1, 0.3), (2, 0.1), (3, 0.1), (4, 0.3), (5, 0.2)]),
+        };
+
+        assert_eq!(3.5, bimodal.median());
+
+        // Even odds of 2 or less, with nothing rolled between 2 and 6.
+        let gap = Distribution {
+            outcomes: BTreeMap::from([(1, 0.25), (2, 0.25), (6, 0.25), (7, 0.25)]),
+        };
+
+        assert_eq!(4.0, gap.median());
+    }
+
+    #[test]
+    fn test_operators() {
+        let d6 = Distribution::uniform(1, 6);
+        let three_d6 = d6.clone() + d6.clone() + d6.clone();
+
+        assert_eq!((3, 18), (three_d6.min(), three_d6.max()));
+        assert_close(10.5, three_d6.mean());
+        assert_eq!(two_d6().convolve(&d6), three_d6);
+
+        let modified = three_d6.clone() + 2;
+
+        assert_eq!((5, 20), (modified.min(), modified.max()));
+        assert_close(12.5, modified.mean());
+
+        let negated = -three_d6.clone();
+
+        assert_eq!((-18, -3), (negated.min(), negated.max()));
+        assert_close(-10.5, negated.mean());
+        assert_eq!(three_d6.probability(10), negated.probability(-10));
+    }
+}
@@ -0,0 +1,1068 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    ast::{BinaryOp, Condition, Expr, KeepDropKind, PoolModifier, RerollStrategy, UnaryOp},
+    distribution::Distribution,
+};
+
+/// An expression that has no distribution, because some way of rolling it
+/// can't be evaluated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvalError {
+    /// A divisor that can be 0, as in `6 / (d2 - 1)`.
+    DivisionByZero,
+    /// A die that can have this many sides, which is fewer than one.
+    TooFewSides(i32),
+    /// Arithmetic whose result can be too big or too small for an `i32`, as
+    /// in `d6 * 1000000000`.
+    Overflow,
+    /// An explosion of something that already explodes, like `(2d6!)!`. The
+    /// faces of an exploding die run into the hundreds, and exploding each of
+    /// those in turn is far too slow to work out.
+    NestedExplode,
+    /// A `max` or `min` with nothing to pick from, which can only come from
+    /// building an [`Expr::Max`] or [`Expr::Min`] by hand.
+    NoArguments,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::TooFewSides(sides) => {
+                write!(f, "a die needs at least one side, not {sides}")
+            }
+            EvalError::Overflow => write!(f, "arithmetic overflow"),
+            EvalError::NestedExplode => write!(f, "can't explode dice that already explode"),
+            EvalError::NoArguments => write!(f, "max and min need at least one argument"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Computes the exact distribution of every value `expr` can take.
+///
+/// Comparisons are 1 when they hold and 0 otherwise, and division truncates
+/// toward zero, so `-7 / 2` is -3. A dice count of zero or less rolls no dice
+/// and totals 0.
+///
+/// Rather than leave out the rolls that can't be evaluated, like those where
+/// a divisor comes up 0, the whole expression is an error if any roll is.
+pub fn distribution(expr: &Expr) -> Result<Distribution, EvalError> {
+    let result = match expr {
+        Expr::Int(n) => Distribution::constant(*n),
+        Expr::Dice { count, sides } => dice(&distribution(count)?, &distribution(sides)?)?,
+        Expr::BinaryOp { left, op, right } => {
+            let lhs = distribution(left)?;
+            let rhs = distribution(right)?;
+
+            if *op == BinaryOp::Div && rhs.probability(0) > 0.0 {
+                return Err(EvalError::DivisionByZero);
+            }
+
+            lhs.try_combine(&rhs, |a, b| op.apply(a, b))
+                .ok_or(EvalError::Overflow)?
+        }
+        Expr::UnaryOp {
+            op: UnaryOp::Neg,
+            operand,
+        } => distribution(operand)?
+            .try_map(i32::checked_neg)
+            .ok_or(EvalError::Overflow)?,
+        Expr::Comparison { op, lhs, rhs } if lhs.counts_successes() => {
+            let mut outcomes = BTreeMap::new();
+            let target = distribution(rhs)?;
+
+            for (count, die, p) in dice_pool(lhs)? {
+                for (v, q) in target.outcomes() {
+                    let success: f64 = die
+                        .outcomes()
+                        .filter(|&(face, _)| op.holds(face, v))
+                        .map(|(_, r)| r)
+                        .sum();
+
+                    for (n, r) in binomial(count, success).outcomes() {
+                        *outcomes.entry(n).or_insert(0.0) += p * q * r;
+                    }
+                }
+            }
+
+            Distribution { outcomes }
+        }
+        Expr::Comparison { op, lhs, rhs } => {
+            distribution(lhs)?.combine(&distribution(rhs)?, |a, b| i32::from(op.holds(a, b)))
+        }
+        Expr::DicePool {
+            count,
+            sides,
+            modifier,
+        } => {
+            let pools = uniform_pools(count, sides)?;
+
+            match modifier {
+                PoolModifier::KeepHighest(n) => {
+                    keep_dice(pools, &distribution(n)?, true, |_, keep| Some(keep))?
+                }
+                PoolModifier::KeepLowest(n) => {
+                    keep_dice(pools, &distribution(n)?, false, |_, keep| Some(keep))?
+                }
+                PoolModifier::DropHighest(n) => {
+                    keep_dice(pools, &distribution(n)?, false, i32::checked_sub)?
+                }
+                PoolModifier::DropLowest(n) => {
+                    keep_dice(pools, &distribution(n)?, true, i32::checked_sub)?
+                }
+                PoolModifier::None => dice(&distribution(count)?, &distribution(sides)?)?,
+            }
+        }
+        Expr::Keep { pool, count } => {
+            keep_dice(dice_pool(pool)?, &distribution(count)?, true, |_, keep| {
+                Some(keep)
+            })?
+        }
+        Expr::Drop { pool, count } => keep_dice(
+            dice_pool(pool)?,
+            &distribution(count)?,
+            true,
+            i32::checked_sub,
+        )?,
+        Expr::KeepDrop { inner, kind, count } => {
+            let pools = dice_pool(inner)?;
+            let count = distribution(count)?;
+
+            match kind {
+                KeepDropKind::KeepHighest => keep_dice(pools, &count, true, |_, keep| Some(keep))?,
+                KeepDropKind::KeepLowest => keep_dice(pools, &count, false, |_, keep| Some(keep))?,
+                KeepDropKind::DropHighest => keep_dice(pools, &count, false, i32::checked_sub)?,
+                KeepDropKind::DropLowest => keep_dice(pools, &count, true, i32::checked_sub)?,
+            }
+        }
+        Expr::Explode { .. } | Expr::Reroll { .. } => {
+            let mut outcomes = BTreeMap::new();
+
+            for (count, die, p) in dice_pool(expr)? {
+                let mut total = Distribution::constant(0);
+
+                for _ in 0..count {
+                    total = total.convolve(&die);
+                }
+
+                for (n, q) in total.outcomes() {
+                    *outcomes.entry(n).or_insert(0.0) += p * q;
+                }
+            }
+
+            Distribution { outcomes }
+        }
+        Expr::Max(args) => extreme(args, i32::max)?,
+        Expr::Min(args) => extreme(args, i32::min)?,
+        Expr::Group(inner) => distribution(inner)?,
+    };
+
+    Ok(result)
+}
+
+/// One subexpression's distribution, as recorded by [`explain`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// The subexpression as source text.
+    pub expr: String,
+    pub mean: f64,
+    pub min: i32,
+    pub max: i32,
+}
+
+/// Computes the distribution of `expr` like [`distribution`], along with a
+/// [`Step`] for each subexpression on the way, innermost first and ending with
+/// `expr` itself.
+///
+/// Number literals and parentheses are left out as they add nothing, and dice
+/// like `2d6` are preceded by a single die like `d6`.
+pub fn explain(expr: &Expr) -> Result<(Distribution, Vec<Step>), EvalError> {
+    let mut steps = Vec::new();
+    let result = trace(expr, &mut steps)?;
+
+    Ok((result, steps))
+}
+
+fn trace(expr: &Expr, steps: &mut Vec<Step>) -> Result<Distribution, EvalError> {
+    for child in expr.children() {
+        trace(child, steps)?;
+    }
+
+    if let Expr::Dice { count, sides } = expr {
+        if **count != Expr::Int(1) {
+            trace(
+                &Expr::Dice {
+                    count: Box::new(Expr::Int(1)),
+                    sides: sides.clone(),
+                },
+                steps,
+            )?;
+        }
+    }
+
+    let result = distribution(expr)?;
+
+    if !matches!(expr, Expr::Int(_) | Expr::Group(_)) {
+        steps.push(Step {
+            expr: expr.to_string(),
+            mean: result.mean(),
+            min: result.min(),
+            max: result.max(),
+        });
+    }
+
+    Ok(result)
+}
+
+/// The distribution of `pick` applied across independent rolls of every one of
+/// `args`, such as the highest of them.
+fn extreme(args: &[Expr], pick: fn(i32, i32) -> i32) -> Result<Distribution, EvalError> {
+    let mut args = args.iter().map(distribution);
+    let first = args.next().ok_or(EvalError::NoArguments)??;
+
+    args.try_fold(first, |result, arg| Ok(result.combine(&arg?, pick)))
+}
+
+/// The distribution of the total of `count` dice with `sides` sides each,
+/// where both may themselves vary.
+fn dice(count: &Distribution, sides: &Distribution) -> Result<Distribution, EvalError> {
+    let mut outcomes = BTreeMap::new();
+
+    for (s, p) in sides.outcomes() {
+        if s < 1 {
+            return Err(EvalError::TooFewSides(s));
+        }
+
+        let die = Distribution::uniform(1, s);
+        let mut total = Distribution::constant(0);
+        let mut rolled = 0;
+
+        for (c, q) in count.outcomes() {
+            while rolled < c {
+                total = total.convolve(&die);
+                rolled += 1;
+            }
+
+            for (n, r) in total.outcomes() {
+                *outcomes.entry(n).or_insert(0.0) += p * q * r;
+            }
+        }
+    }
+
+    Ok(Distribution { outcomes })
+}
+
+/// The distribution of the sum of the highest dice in `pools`, or the lowest if
+/// `highest` is false, where `kept` turns the size of a pool and a value of
+/// `operand` into how many dice to keep, or `None` if that overflows.
+fn keep_dice(
+    pools: Vec<(i32, Distribution, f64)>,
+    operand: &Distribution,
+    highest: bool,
+    kept: impl Fn(i32, i32) -> Option<i32>,
+) -> Result<Distribution, EvalError> {
+    let mut outcomes = BTreeMap::new();
+
+    for (count, die, p) in pools {
+        for (k, q) in operand.outcomes() {
+            let keep = kept(count, k).ok_or(EvalError::Overflow)?;
+
+            for (n, r) in sum_of_kept(count, &die, keep, highest).outcomes() {
+                *outcomes.entry(n).or_insert(0.0) += p * q * r;
+            }
+        }
+    }
+
+    Ok(Distribution { outcomes })
+}
+
+/// Every way `expr` can roll a pool of identical dice, as the number of dice,
+/// the distribution of a single die, and the probability of that pool.
+fn dice_pool(expr: &Expr) -> Result<Vec<(i32, Distribution, f64)>, EvalError> {
+    let pools = match expr {
+        Expr::Dice { count, sides } => uniform_pools(count, sides)?,
+        Expr::Explode { inner, .. } if inner.contains_explode() => {
+            return Err(EvalError::NestedExplode);
+        }
+        Expr::Explode {
+            inner,
+            threshold: None,
+            depth,
+        } => dice_pool(inner)?
+            .into_iter()
+            .map(|(count, die, p)| {
+                let highest = die.max();
+                (count, explode(&die, |face| face == highest, *depth), p)
+            })
+            .collect(),
+        Expr::Explode {
+            inner,
+            threshold: Some(Condition { op, value }),
+            depth,
+        } => {
+            let pools = dice_pool(inner)?;
+            let mut exploded = Vec::new();
+
+            for (v, q) in distribution(value)?.outcomes() {
+                for (count, die, p) in &pools {
+                    let die = explode(die, |face| op.holds(face, v), *depth);
+                    exploded.push((*count, die, p * q));
+                }
+            }
+
+            exploded
+        }
+        Expr::Reroll {
+            inner,
+            condition: Condition { op, value },
+            strategy,
+        } => {
+            let pools = dice_pool(inner)?;
+            let mut rerolled = Vec::new();
+
+            for (v, q) in distribution(value)?.outcomes() {
+                for (count, die, p) in &pools {
+                    let die = reroll(die, |face| op.holds(face, v), *strategy);
+                    rerolled.push((*count, die, p * q));
+                }
+            }
+
+            rerolled
+        }
+        Expr::Group(inner) => dice_pool(inner)?,
+        _ => vec![(1, distribution(expr)?, 1.0)],
+    };
+
+    Ok(pools)
+}
+
+/// Every way of rolling `count` dice with `sides` sides each, in the form
+/// [`dice_pool`] returns.
+fn uniform_pools(count: &Expr, sides: &Expr) -> Result<Vec<(i32, Distribution, f64)>, EvalError> {
+    let count = distribution(count)?;
+    let mut pools = Vec::new();
+
+    for (s, p) in distribution(sides)?.outcomes() {
+        if s < 1 {
+            return Err(EvalError::TooFewSides(s));
+        }
+
+        for (c, q) in count.outcomes() {
+            pools.push((c.max(0), Distribution::uniform(1, s), p * q));
+        }
+    }
+
+    Ok(pools)
+}
+
+/// The distribution of a single exploding `die`, which is rolled again and
+/// added each time it shows a face that `triggers` it, up to `depth` times.
+///
+/// A roll that still explodes after the last reroll would keep going, so
+/// rather than count it as final, that sliver of probability is dropped and
+/// the rest scaled back up to sum to 1. A die where every face explodes would
+/// never stop and is left alone.
+fn explode(die: &Distribution, triggers: impl Fn(i32) -> bool, depth: u32) -> Distribution {
+    let (exploding, stopping): (Vec<_>, Vec<_>) =
+        die.outcomes().partition(|&(face, _)| triggers(face));
+
+    if exploding.is_empty() || stopping.is_empty() {
+        return die.clone();
+    }
+
+    let mut outcomes = BTreeMap::new();
+    // The totals of the faces that have exploded so far, weighted by the
+    // chance of getting that far.
+    let mut exploded = BTreeMap::from([(0, 1.0)]);
+
+    for level in 0..=depth {
+        for (&total, &p) in &exploded {
+            for &(face, q) in &stopping {
+                *outcomes.entry(total + face).or_insert(0.0) += p * q;
+            }
+        }
+
+        if level < depth {
+            let mut next = BTreeMap::new();
+
+            for (&total, &p) in &exploded {
+                for &(face, q) in &exploding {
+                    *next.entry(total + face).or_insert(0.0) += p * q;
+                }
+            }
+
+            exploded = next;
+        }
+    }
+
+    let total: f64 = outcomes.values().sum();
+
+    for p in outcomes.values_mut() {
+        *p /= total;
+    }
+
+    Distribution { outcomes }
+}
+
+/// The distribution of the number of successes in `count` independent tries
+/// that each succeed with probability `p`.
+fn binomial(count: i32, p: f64) -> Distribution {
+    let mut outcomes = BTreeMap::new();
+    // C(count, k) * p^k * (1 - p)^(count - k), built up one `k` at a time.
+    let mut choose = 1.0;
+
+    for k in 0..=count {
+        let q = choose * p.powi(k) * (1.0 - p).powi(count - k);
+
+        if q > 0.0 {
+            outcomes.insert(k, q);
+        }
+
+        choose *= f64::from(count - k) / f64::from(k + 1);
+    }
+
+    Distribution { outcomes }
+}
+
+/// The distribution of a single `die` that is rolled again when it shows a face
+/// that `triggers` it, as `strategy` says. Rerolling until no face triggers
+/// would never stop on a die where every face does, so that die is left alone.
+fn reroll(
+    die: &Distribution,
+    triggers: impl Fn(i32) -> bool,
+    strategy: RerollStrategy,
+) -> Distribution {
+    if die.outcomes().all(|(face, _)| triggers(face)) {
+        return die.clone();
+    }
+
+    let rerolled: f64 = die
+        .outcomes()
+        .filter(|&(face, _)| triggers(face))
+        .map(|(_, p)| p)
+        .sum();
+
+    let outcomes = die
+        .outcomes()
+        .map(|(face, p)| {
+            let kept = if triggers(face) { 0.0 } else { p };
+
+            match strategy {
+                RerollStrategy::Once => (face, kept + rerolled * p),
+                RerollStrategy::Until => (face, kept / (1.0 - rerolled)),
+            }
+        })
+        .filter(|&(_, p)| p > 0.0)
+        .collect();
+
+    Distribution { outcomes }
+}
+
+/// The distribution of the sum of the highest `keep` of `count` independent
+/// rolls of `die`, or the lowest if `highest` is false.
+fn sum_of_kept(count: i32, die: &Distribution, keep: i32, highest: bool) -> Distribution {
+    // Rather than enumerate every roll, walk the faces starting from the end
+    // being kept and choose how many of the remaining dice land on each. A
+    // state is the number of dice placed so far and the sum of those kept,
+    // and the weight of placing `j` of the `r` remaining dice on a face with
+    // probability `p` is C(r, j) * p^j.
+    let keep = keep.clamp(0, count);
+    let mut states = BTreeMap::from([((0, 0), 1.0)]);
+    let mut faces: Vec<(i32, f64)> = die.outcomes().collect();
+
+    if highest {
+        faces.reverse();
+    }
+
+    for (face, p) in faces {
+        let mut next = BTreeMap::new();
+
+        for ((placed, sum), q) in states {
+            let remaining = count - placed;
+            let mut weight = 1.0;
+
+            for j in 0..=remaining {
+                let kept = j.min((keep - placed).max(0));
+                *next.entry((placed + j, sum + kept * face)).or_insert(0.0) += q * weight;
+                weight *= p * f64::from(remaining - j) / f64::from(j + 1);
+            }
+        }
+
+        states = next;
+    }
+
+    let mut outcomes = BTreeMap::new();
+
+    for ((placed, sum), p) in states {
+        if placed == count {
+            *outcomes.entry(sum).or_insert(0.0) += p;
+        }
+    }
+
+    Distribution { outcomes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, tokenize_strict};
+
+    fn distribution_of(input: &str) -> Distribution {
+        distribution(&parse(&tokenize_strict(input).unwrap()).unwrap()).unwrap()
+    }
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-12,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_constant() {
+        let outcomes: Vec<(i32, f64)> = distribution_of("3").outcomes().collect();
+
+        assert_eq!(vec![(3, 1.0)], outcomes);
+    }
+
+    #[test]
+    fn test_single_die() {
+        let d6 = distribution_of("d6");
+
+        assert_eq!(6, d6.outcomes().count());
+
+        for face in 1..=6 {
+            assert_close(1.0 / 6.0, d6.probability(face));
+        }
+    }
+
+    #[test]
+    fn test_two_dice() {
+        let two_d6 = distribution_of("2d6");
+
+        assert_eq!(11, two_d6.outcomes().count());
+        assert_close(6.0 / 36.0, two_d6.probability(7));
+        assert_close(1.0 / 36.0, two_d6.probability(2));
+        assert_close(1.0 / 36.0, two_d6.probability(12));
+        assert_eq!(0.0, two_d6.probability(13));
+    }
+
+    #[test]
+    fn test_probabilities_sum_to_one() {
+        let total: f64 = distribution_of("3d6 * 2 - d4")
+            .outcomes()
+            .map(|(_, p)| p)
+            .sum();
+
+        assert_close(1.0, total);
+    }
+
+    #[test]
+    fn test_comparison() {
+        let at_least_eleven = distribution_of("d20 >= 11");
+
+        assert_close(0.5, at_least_eleven.probability(1));
+        assert_close(0.5, at_least_eleven.probability(0));
+
+        let three = distribution_of("d6 == 3");
+
+        assert_eq!(
+            vec![0, 1],
+            three.outcomes().map(|(n, _)| n).collect::<Vec<_>>()
+        );
+        assert_close(1.0 / 6.0, three.probability(1));
+    }
+
+    #[test]
+    fn test_comparisons_compose() {
+        // Two attacks that each hit on an 11 or better.
+        let hits = distribution_of("(d20 >= 11) + (d20 >= 11)");
+
+        assert_close(0.25, hits.probability(0));
+        assert_close(0.5, hits.probability(1));
+        assert_close(0.25, hits.probability(2));
+    }
+
+    #[test]
+    fn test_negation() {
+        let outcomes: Vec<i32> = distribution_of("-d4").outcomes().map(|(n, _)| n).collect();
+
+        assert_eq!(vec![-4, -3, -2, -1], outcomes);
+    }
+
+    #[test]
+    fn test_negative_literals() {
+        assert_eq!(Distribution::constant(-5), distribution_of("-5"));
+        assert_eq!(Distribution::constant(5), distribution_of("3 - -2"));
+
+        let negated = distribution_of("-2d6");
+        let rolled = distribution_of("2d6");
+
+        assert_eq!((-12, -2), (negated.min(), negated.max()));
+        assert_close(-7.0, negated.mean());
+
+        for (n, p) in rolled.outcomes() {
+            assert_eq!(p, negated.probability(-n));
+        }
+    }
+
+    #[test]
+    fn test_variable_dice_count() {
+        // The first d2 decides whether to roll one d2 or two.
+        let rolled = distribution_of("(d2)d2");
+
+        assert_close(0.25, rolled.probability(1));
+        assert_close(0.375, rolled.probability(2));
+        assert_close(0.25, rolled.probability(3));
+        assert_close(0.125, rolled.probability(4));
+    }
+
+    #[test]
+    fn test_mean() {
+        assert_eq!(4.0, distribution_of("4").mean());
+        assert_eq!(3.5, distribution_of("d6").mean());
+        assert_close(10.5, distribution_of("d20").mean());
+        assert_close(10.0, distribution_of("2d6+3").mean());
+    }
+
+    #[test]
+    fn test_variance() {
+        assert_eq!(0.0, distribution_of("7").variance());
+        assert_close(35.0 / 12.0, distribution_of("d6").variance());
+        assert_close(35.0 / 6.0, distribution_of("2d6").variance());
+        assert_close(
+            (35.0f64 / 6.0).sqrt(),
+            distribution_of("2d6").standard_deviation(),
+        );
+    }
+
+    #[test]
+    fn test_keep_highest() {
+        let ability_score = distribution_of("4d6k3");
+
+        assert_close(15869.0 / 1296.0, ability_score.mean());
+        assert!((ability_score.mean() - 12.2446).abs() < 1e-4);
+        assert_close(1.0 / 1296.0, ability_score.probability(3));
+        assert_close(21.0 / 1296.0, ability_score.probability(18));
+    }
+
+    #[test]
+    fn test_keep_highest_of_two() {
+        let advantage = distribution_of("2d20k1");
+
+        assert_close(39.0 / 400.0, advantage.probability(20));
+        assert_close(1.0 / 400.0, advantage.probability(1));
+    }
+
+    #[test]
+    fn test_keep_everything_or_nothing() {
+        assert_eq!(distribution_of("3d6").outcomes().count(), 16);
+
+        for (n, p) in distribution_of("3d6").outcomes() {
+            assert_close(p, distribution_of("3d6k5").probability(n));
+        }
+
+        assert_eq!(distribution_of("0"), distribution_of("3d6k0"));
+    }
+
+    #[test]
+    fn test_drop_lowest() {
+        let dropped = distribution_of("4d6 drop 1");
+        let kept = distribution_of("4d6k3");
+
+        assert_eq!(kept.outcomes().count(), dropped.outcomes().count());
+
+        for (n, p) in kept.outcomes() {
+            assert_close(p, dropped.probability(n));
+        }
+    }
+
+    #[test]
+    fn test_drop_everything() {
+        assert_eq!(distribution_of("0"), distribution_of("4d6 drop 4"));
+        assert_eq!(distribution_of("0"), distribution_of("4d6 drop 9"));
+    }
+
+    #[test]
+    fn test_exploding_die() {
+        let exploding = distribution_of("d6!");
+
+        assert_eq!(0.0, exploding.probability(6));
+        assert_eq!(0.0, exploding.probability(12));
+
+        for n in 7..=11 {
+            assert_close(1.0 / 36.0, exploding.probability(n));
+        }
+
+        assert_close(1.0 / 216.0, exploding.probability(13));
+        assert_close(4.2, exploding.mean());
+    }
+
+    #[test]
+    fn test_exploding_pool_explodes_each_die() {
+        let exploding = distribution_of("2d6!");
+
+        // A 7 needs two faces below 6 adding up to it, since an exploded
+        // die is already at least 7.
+        assert_close(1.0 / 36.0, exploding.probability(2));
+        assert_close(4.0 / 36.0, exploding.probability(7));
+        assert_close(8.4, exploding.mean());
+    }
+
+    #[test]
+    fn test_explosion_depth() {
+        let die = Expr::Dice {
+            count: Box::new(Expr::Int(1)),
+            sides: Box::new(Expr::Int(6)),
+        };
+        let once = distribution(&Expr::Explode {
+            inner: Box::new(die),
+            threshold: None,
+            depth: 1,
+        })
+        .unwrap();
+
+        assert_eq!(Some(11), once.outcomes().map(|(n, _)| n).last());
+        assert_close(1.0, once.outcomes().map(|(_, p)| p).sum());
+    }
+
+    #[test]
+    fn test_min_and_max() {
+        let range = distribution_of("2d6 - 1");
+
+        assert_eq!(1, range.min());
+        assert_eq!(11, range.max());
+    }
+
+    #[test]
+    fn test_dice_pool_modifiers() {
+        let assert_same = |a: &str, b: &str| {
+            let (a, b) = (distribution_of(a), distribution_of(b));
+
+            assert_eq!(a.outcomes().count(), b.outcomes().count());
+
+            for (n, p) in a.outcomes() {
+                assert_close(p, b.probability(n));
+            }
+        };
+
+        assert_same("4d6kh3", "4d6k3");
+        assert_same("4d6dl1", "4d6k3");
+        assert_same("4d6kl3", "4d6dh1");
+        assert_same("2d20kl1", "21 - 2d20kh1");
+    }
+
+    #[test]
+    fn test_dice_pool_drop_overflow() {
+        for input in ["4d6dl(-2147483647 - 1)", "4d6dh(-2147483647 - 1)"] {
+            let expr = parse(&tokenize_strict(input).unwrap()).unwrap();
+
+            assert_eq!(Err(EvalError::Overflow), distribution(&expr), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_keep_lowest() {
+        let disadvantage = distribution_of("2d20kl1");
+
+        assert_close(39.0 / 400.0, disadvantage.probability(1));
+        assert_close(1.0 / 400.0, disadvantage.probability(20));
+    }
+
+    #[test]
+    fn test_explode_on_threshold() {
+        let exploding = distribution_of("d6 explode on >= 5");
+
+        assert_eq!(0.0, exploding.probability(5));
+        assert_close(1.0 / 6.0, exploding.probability(4));
+        // Only a 5 followed by a 1 totals 6.
+        assert_close(1.0 / 36.0, exploding.probability(5 + 1));
+        // Each roll explodes a third of the time, so on average there are 1.5
+        // rolls of a die averaging 3.5.
+        assert_close(5.25, exploding.mean());
+    }
+
+    #[test]
+    fn test_explode_on_every_face_is_left_alone() {
+        assert_eq!(distribution_of("d6"), distribution_of("d6 explode on >= 1"));
+    }
+
+    #[test]
+    fn test_reroll_once() {
+        let rerolled = distribution_of("d6 reroll once <= 2");
+
+        // A 1 only stays by rolling a 1 or 2 and then a 1.
+        assert_close(1.0 / 18.0, rerolled.probability(1));
+        assert_close(1.0 / 6.0 + 1.0 / 18.0, rerolled.probability(6));
+        assert_close(1.0, rerolled.outcomes().map(|(_, p)| p).sum());
+    }
+
+    #[test]
+    fn test_reroll_until() {
+        let rerolled = distribution_of("d6 reroll until <= 2");
+
+        assert_eq!(0.0, rerolled.probability(2));
+        assert_close(0.25, rerolled.probability(3));
+        assert_close(4.5, rerolled.mean());
+    }
+
+    #[test]
+    fn test_reroll_pool() {
+        let rerolled = distribution_of("2d6 reroll until == 1");
+
+        assert_eq!(4, rerolled.min());
+        assert_close(1.0 / 25.0, rerolled.probability(4));
+        assert_close(8.0, rerolled.mean());
+    }
+
+    #[test]
+    fn test_reroll_until_every_face_is_left_alone() {
+        assert_eq!(
+            distribution_of("d6"),
+            distribution_of("d6 reroll until < 7")
+        );
+    }
+
+    #[test]
+    fn test_keep_drop() {
+        assert_eq!(
+            distribution_of("4d6kh3"),
+            distribution_of("4d6 keep high 3")
+        );
+        assert_eq!(
+            distribution_of("4d6dl1"),
+            distribution_of("(4d6) drop low 1")
+        );
+        assert_eq!(distribution_of("2d20kl1"), distribution_of("(2d20)kl1"));
+        assert_eq!(
+            distribution_of("3d6dh1"),
+            distribution_of("3d6 drop high 1")
+        );
+    }
+
+    #[test]
+    fn test_keep_drop_overflow() {
+        for input in [
+            "4d6 drop (-2147483647 - 1)",
+            "(4d6) drop low (-2147483647 - 1)",
+            "(4d6)dh(-2147483647 - 1)",
+        ] {
+            let expr = parse(&tokenize_strict(input).unwrap()).unwrap();
+
+            assert_eq!(Err(EvalError::Overflow), distribution(&expr), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_keep_drop_on_exploding_pool() {
+        let best = distribution_of("(2d6!) keep high 1");
+
+        // Neither die may explode and both must show a 1.
+        assert_close(1.0 / 36.0, best.probability(1));
+        assert_close(1.0, best.outcomes().map(|(_, p)| p).sum());
+        assert!(best.mean() > distribution_of("d6!").mean());
+    }
+
+    #[test]
+    fn test_counting_successes() {
+        let successes = distribution_of("5d10 >= 8");
+
+        assert_eq!(0, successes.min());
+        assert_eq!(5, successes.max());
+        assert_close(1.5, successes.mean());
+        assert_close(0.7_f64.powi(5), successes.probability(0));
+        assert_close(5.0 * 0.3 * 0.7_f64.powi(4), successes.probability(1));
+        assert_close(1.0, successes.outcomes().map(|(_, p)| p).sum());
+    }
+
+    #[test]
+    fn test_grouped_pool_compares_total() {
+        let total = distribution_of("(2d6) >= 7");
+
+        assert_eq!(
+            vec![0, 1],
+            total.outcomes().map(|(n, _)| n).collect::<Vec<_>>()
+        );
+        assert_close(21.0 / 36.0, total.probability(1));
+        assert_close(2.0 / 6.0, distribution_of("2d6 >= 5").mean() / 2.0);
+    }
+
+    #[test]
+    fn test_max_and_min() {
+        let highest = distribution_of("max(d6, d6)");
+        let lowest = distribution_of("min(d6, d6)");
+
+        assert_close(11.0 / 36.0, highest.probability(6));
+        assert_close(1.0 / 36.0, highest.probability(1));
+        assert_close(11.0 / 36.0, lowest.probability(1));
+
+        for n in 1..=6 {
+            assert_close(highest.probability(n), lowest.probability(7 - n));
+        }
+    }
+
+    #[test]
+    fn test_min_clamps() {
+        let clamped = distribution_of("min(d20, 10)");
+
+        assert_eq!((1, 10), (clamped.min(), clamped.max()));
+        assert_close(0.55, clamped.probability(10));
+
+        for n in 1..10 {
+            assert_close(0.05, clamped.probability(n));
+        }
+    }
+
+    #[test]
+    fn test_explode_call() {
+        assert_eq!(distribution_of("2d6!"), distribution_of("explode(2d6)"));
+        assert_eq!(
+            distribution_of("2d6 explode on >= 5"),
+            distribution_of("explode(2d6, 5)")
+        );
+    }
+
+    #[test]
+    fn test_method_chains() {
+        assert_eq!(distribution_of("4d6k3"), distribution_of("4d6.keep(3)"));
+        assert_eq!(
+            distribution_of("4d6 rr == 1 k3"),
+            distribution_of("4d6.reroll(1).keep(3)")
+        );
+        assert_eq!(
+            distribution_of("4d6 rr == 1 k3"),
+            distribution_of("4d6.keep(3).reroll(1)")
+        );
+    }
+
+    #[test]
+    fn test_max_of_many() {
+        let advantage = distribution_of("max(d20, d20)");
+
+        for (n, p) in distribution_of("2d20kh1").outcomes() {
+            assert_close(p, advantage.probability(n));
+        }
+
+        assert_close(
+            1.0 - 0.5_f64.powi(3),
+            distribution_of("max(d2, d2, d2)").probability(2),
+        );
+        assert_eq!(distribution_of("d8"), distribution_of("max(d8)"));
+    }
+
+    #[test]
+    fn test_explain() {
+        let expr = parse(&tokenize_strict("2d6 + 3").unwrap()).unwrap();
+        let (result, steps) = explain(&expr).unwrap();
+
+        assert_eq!(distribution(&expr).unwrap(), result);
+
+        let names: Vec<&str> = steps.iter().map(|step| step.expr.as_str()).collect();
+        assert_eq!(vec!["d6", "2d6", "2d6 + 3"], names);
+
+        assert_close(3.5, steps[0].mean);
+        assert_eq!((1, 6), (steps[0].min, steps[0].max));
+        assert_close(7.0, steps[1].mean);
+        assert_eq!((2, 12), (steps[1].min, steps[1].max));
+        assert_close(10.0, steps[2].mean);
+    }
+
+    #[test]
+    fn test_explain_nested() {
+        let expr = parse(&tokenize_strict("max(d4, (d6)) * 2").unwrap()).unwrap();
+        let names: Vec<String> = explain(&expr)
+            .unwrap()
+            .1
+            .into_iter()
+            .map(|step| step.expr)
+            .collect();
+
+        assert_eq!(
+            vec!["d4", "d6", "max(d4, (d6))", "max(d4, (d6)) * 2"],
+            names
+        );
+    }
+
+    #[test]
+    fn test_division_truncates_toward_zero() {
+        assert_eq!(Distribution::constant(3), distribution_of("7 / 2"));
+        assert_eq!(Distribution::constant(-3), distribution_of("-7 / 2"));
+        assert_eq!(Distribution::constant(-3), distribution_of("7 / -2"));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let divide = |input| distribution(&parse(&tokenize_strict(input).unwrap()).unwrap());
+
+        assert_eq!(Err(EvalError::DivisionByZero), divide("6 / 0"));
+        assert_eq!(Err(EvalError::DivisionByZero), divide("6 / (d2 - 1)"));
+        assert_eq!(
+            Err(EvalError::DivisionByZero),
+            divide("max(1, 2d6 / (d3 - 2))")
+        );
+        assert!(divide("6 / d2").is_ok());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let evaluate = |input| distribution(&parse(&tokenize_strict(input).unwrap()).unwrap());
+
+        for input in [
+            "2147483647 + 1",
+            "-2147483647 - 2",
+            "100000 * 100000",
+            "d6 * 1000000000",
+            "(-2147483647 - 1) / -1",
+            "-(-2147483647 - 1)",
+        ] {
+            assert_eq!(Err(EvalError::Overflow), evaluate(input), "{input}");
+        }
+
+        assert!(evaluate("2147483646 + 1").is_ok());
+    }
+
+    #[test]
+    fn test_no_arguments() {
+        assert_eq!(
+            Err(EvalError::NoArguments),
+            distribution(&Expr::Max(Vec::new()))
+        );
+        assert_eq!(
+            Err(EvalError::NoArguments),
+            distribution(&Expr::Min(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_nested_explode() {
+        let evaluate = |input| distribution(&parse(&tokenize_strict(input).unwrap()).unwrap());
+
+        for input in [
+            "(2d6!) explode on >= 5",
+            "(2d6!)!",
+            "2d6! rr <= 3 !",
+            "(2d6! + 1) explode on >= 5",
+        ] {
+            assert_eq!(Err(EvalError::NestedExplode), evaluate(input), "{input}");
+        }
+
+        assert!(evaluate("(2d6!) + d4!").is_ok());
+    }
+
+    #[test]
+    fn test_too_few_sides() {
+        let expr = parse(&tokenize_strict("2d(d3 - 2)").unwrap()).unwrap();
+
+        assert!(matches!(
+            distribution(&expr),
+            Err(EvalError::TooFewSides(_))
+        ));
+        assert_eq!(
+            "a die needs at least one side, not 0",
+            EvalError::TooFewSides(0).to_string()
+        );
+    }
+}
@@ -1 +1,15 @@
+pub mod ast;
+pub mod distribution;
+pub mod eval;
+pub mod parser;
+pub mod pretty;
+pub mod roll;
 pub mod token;
+pub mod visitor;
+
+pub use distribution::{ComparisonResult, Distribution};
+pub use eval::{distribution, explain, EvalError};
+pub use parser::{parse, parse_all, Parser};
+pub use roll::{roll, Rng, XorShift};
+pub use token::{tokenize, tokenize_strict};
+pub use visitor::ExprVisitor;
@@ -1,22 +1,242 @@
-use dice_stats_lang::token;
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    process,
+};
+
+use dice_stats_lang::{
+    ast::Expr, distribution, explain, token::Span, tokenize_strict, Distribution, EvalError, Parser,
+};
+
+/// A lex or parse failure, positioned within the source it came from.
+struct Error {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+/// How each distribution is printed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Summary,
+    /// The summary followed by a histogram.
+    Histogram,
+    /// Each step of the evaluation, then the summary.
+    Explain,
+    Json,
+}
+
+/// How many characters the longest bar of a histogram takes up.
+const HISTOGRAM_WIDTH: usize = 40;
 
 fn main() {
-    let input = "( ) { } + - * / . ! -1 die foo d";
-    let mut input_chars = input.chars().peekable();
+    // The format, along with the flag that chose it.
+    let mut format: Option<(&str, Format)> = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let (flag, chosen) = match arg.as_str() {
+            "--format" => (
+                "--format",
+                match args.next().as_deref() {
+                    Some("text") => Format::Summary,
+                    Some("json") => Format::Json,
+                    Some(other) => usage_error(&format!("unknown format '{other}'")),
+                    None => usage_error("expected a format after '--format'"),
+                },
+            ),
+            "--hist" => ("--hist", Format::Histogram),
+            "--explain" => ("--explain", Format::Explain),
+            _ if path.is_none() => {
+                path = Some(arg);
+                continue;
+            }
+            _ => usage_error(&format!("unexpected argument '{arg}'")),
+        };
+
+        match format {
+            Some((previous, earlier)) if earlier != chosen => {
+                usage_error(&format!("'{flag}' conflicts with '{previous}'"))
+            }
+            _ => format = Some((flag, chosen)),
+        }
+    }
+
+    let format = format.map_or(Format::Summary, |(_, format)| format);
+
+    match path {
+        Some(path) => run_file(&path, format),
+        None => repl(format),
+    }
+}
 
-    let mut current_line = 1;
-    let mut current_column = 1;
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {message}");
+    eprintln!("usage: dice-stats-lang [--format text|json] [--hist] [--explain] [file]");
+    process::exit(2);
+}
 
-    while input_chars.peek().is_some() {
-        let token = token::read_token(&mut input_chars, &mut current_line, &mut current_column);
+/// Evaluates each expression in the file at `path`, printing a summary of
+/// each. Expressions end at a `;` or line break just as in the REPL, but may
+/// carry on over several lines, as may block comments. As JSON, each
+/// expression's distribution is printed on a line of its own.
+fn run_file(path: &str, format: Format) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: couldn't read {path}: {error}");
+            process::exit(1);
+        }
+    };
 
-        match token {
-            Ok(token) => {
-                dbg!(token);
+    let exprs = match parse_source(&source) {
+        Ok(exprs) => exprs,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("[{}:{}] error: {}", error.line, error.column, error.message);
             }
-            Err(token) => {
-                dbg!(token);
+
+            process::exit(1);
+        }
+    };
+
+    let mut failed = false;
+
+    for (index, (expr, span)) in exprs.iter().enumerate() {
+        if !matches!(format, Format::Json) {
+            if index > 0 {
+                println!();
             }
+
+            println!("{}", span.text(&source));
         }
+
+        if let Err(error) = print(expr, format) {
+            let line = source[..span.start].matches('\n').count() + 1;
+            eprintln!("[{line}] error: {error}");
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
     }
 }
+
+fn repl(format: Format) {
+    let mut lines = io::stdin().lock().lines();
+
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("failed to write to stdout");
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(error)) => {
+                eprintln!("error: couldn't read from stdin: {error}");
+                process::exit(1);
+            }
+            None => break,
+        };
+
+        match line.trim() {
+            "quit" | "exit" => break,
+            input => match parse_line(input) {
+                Ok(exprs) => {
+                    if let Err(error) = print_all(&exprs, format) {
+                        eprintln!("error: {error}");
+                    }
+                }
+                Err(errors) => {
+                    for error in errors {
+                        eprintln!("[{}:{}] error: {}", error.line, error.column, error.message);
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Parses each `;`-separated expression in `input`, giving none if it's only
+/// whitespace and comments.
+fn parse_line(input: &str) -> Result<Vec<Expr>, Vec<Error>> {
+    let exprs = parse_source(input)?;
+    Ok(exprs.into_iter().map(|(expr, _)| expr).collect())
+}
+
+/// Parses every expression in `source` along with the span it came from.
+fn parse_source(source: &str) -> Result<Vec<(Expr, Span)>, Vec<Error>> {
+    let tokens = tokenize_strict(source).map_err(|error| {
+        vec![Error {
+            line: error.line(),
+            column: error.column(),
+            message: error.to_string(),
+        }]
+    })?;
+
+    Parser::new(tokens)
+        .parse_all_with_spans()
+        .map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|error| Error {
+                    line: error.line,
+                    column: error.column,
+                    message: error.message,
+                })
+                .collect()
+        })
+}
+
+/// Evaluates and prints each of `exprs`, with a blank line between them,
+/// stopping at the first that can't be evaluated.
+fn print_all(exprs: &[Expr], format: Format) -> Result<(), EvalError> {
+    for (index, expr) in exprs.iter().enumerate() {
+        if index > 0 && !matches!(format, Format::Json) {
+            println!();
+        }
+
+        print(expr, format)?;
+    }
+
+    Ok(())
+}
+
+fn print(expr: &Expr, format: Format) -> Result<(), EvalError> {
+    match format {
+        Format::Summary => print_summary(&distribution(expr)?),
+        Format::Histogram => {
+            let distribution = distribution(expr)?;
+
+            print_summary(&distribution);
+            print!("{}", distribution.histogram(HISTOGRAM_WIDTH));
+        }
+        Format::Explain => {
+            let (distribution, steps) = explain(expr)?;
+
+            for step in steps {
+                println!(
+                    "{}: mean {:.2}, range {}..={}",
+                    step.expr, step.mean, step.min, step.max
+                );
+            }
+
+            print_summary(&distribution);
+        }
+        Format::Json => println!("{}", distribution(expr)?.to_json()),
+    }
+
+    Ok(())
+}
+
+fn print_summary(distribution: &Distribution) {
+    println!("mean: {:.2}", distribution.mean());
+    println!("min: {}", distribution.min());
+    println!("max: {}", distribution.max());
+    println!("std dev: {:.2}", distribution.standard_deviation());
+    println!("median: {:.2}", distribution.median());
+
+    let modes: Vec<String> = distribution.mode().iter().map(i32::to_string).collect();
+    println!("mode: {}", modes.join(", "));
+}
@@ -0,0 +1,1465 @@
+use std::fmt;
+
+use crate::{
+    ast::{BinaryOp, Condition, Expr, KeepDropKind, PoolModifier, RerollStrategy, UnaryOp},
+    token::{Span, Token, TokenType},
+};
+
+/// A parse failure, positioned at the token that caused it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    /// The tokens that would have been accepted instead, when there are only
+    /// a few. Empty when almost anything could have come next.
+    pub expected: Vec<TokenType>,
+    pub found: TokenType,
+}
+
+/// The broad kinds of [`ParseError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A token that can't go where it is.
+    UnexpectedToken,
+    /// Input that ends partway through an expression, like `2d6 +`. As there's
+    /// no token at the end to point to, the error is positioned at the last
+    /// token before it.
+    UnexpectedEof,
+}
+
+impl ParseError {
+    /// An error for `found` appearing where one of `expected` should have.
+    pub fn unexpected(found: &Token, expected: &[TokenType]) -> Self {
+        let message = match expected {
+            [] => format!("unexpected '{}'", found.token_type),
+            _ => {
+                let expected: Vec<String> = expected
+                    .iter()
+                    .map(|token_type| format!("'{token_type}'"))
+                    .collect();
+
+                format!(
+                    "expected {}, found '{}'",
+                    expected.join(" or "),
+                    found.token_type
+                )
+            }
+        };
+
+        Self {
+            expected: expected.to_vec(),
+            ..Self::at(found, message)
+        }
+    }
+
+    fn at(token: &Token, message: impl Into<String>) -> Self {
+        Self {
+            kind: ParseErrorKind::UnexpectedToken,
+            message: message.into(),
+            line: token.line,
+            column: token.column,
+            expected: Vec::new(),
+            found: token.token_type.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Precedence-climbing parser over a token stream, as produced by
+/// [`tokenize_strict`](crate::token::tokenize_strict).
+pub struct Parser {
+    tokens: Vec<Token>,
+    current: usize,
+    /// How many parentheses are open, as a line break inside them never ends
+    /// an expression.
+    depth: usize,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+        }
+    }
+
+    /// Parses the tokens as a single expression. Anything left over before
+    /// `Eof` is an error.
+    pub fn parse(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression()?;
+
+        match self.peek() {
+            Some(token) if token.token_type != TokenType::Eof => {
+                Err(ParseError::unexpected(token, &[]))
+            }
+            _ => Ok(expr),
+        }
+    }
+
+    /// Parses the tokens as a sequence of expressions, each ending at a `;` or
+    /// a line break. A line break outside parentheses ends an expression
+    /// unless the next line starts with a token that can only carry it on, so
+    /// `2d6` followed by `+ 1` on the next line is one expression, but `2d6`
+    /// followed by `d20` or `-1` is two.
+    ///
+    /// Rather than stop at the first error, this skips to the start of the
+    /// next expression and carries on, so every error is reported at once.
+    pub fn parse_all(&mut self) -> Result<Vec<Expr>, Vec<ParseError>> {
+        let exprs = self.parse_all_with_spans()?;
+        Ok(exprs.into_iter().map(|(expr, _)| expr).collect())
+    }
+
+    /// Like [`Parser::parse_all`], but also gives the span of the source each
+    /// expression was parsed from, not counting the `;` after it.
+    pub fn parse_all_with_spans(&mut self) -> Result<Vec<(Expr, Span)>, Vec<ParseError>> {
+        let mut exprs = Vec::new();
+        let mut errors = Vec::new();
+
+        loop {
+            while self.check(&TokenType::Semicolon) {
+                self.advance();
+            }
+
+            if self.peek().is_none() || self.check(&TokenType::Eof) {
+                break;
+            }
+
+            match self.statement() {
+                Ok(expr) => exprs.push(expr),
+                Err(error) => {
+                    self.depth = 0;
+                    self.synchronize(error.line);
+                    errors.push(error);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(exprs)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses one expression of [`Parser::parse_all`] along with the `;` after
+    /// it, if there is one.
+    fn statement(&mut self) -> Result<(Expr, Span), ParseError> {
+        let start = self.peek().map_or(0, |token| token.span.start);
+        let expr = self.expression()?;
+        let last = &self.tokens[self.current - 1];
+        let (last_line, span) = (last.line, Span::new(start, last.span.end));
+
+        match self.peek() {
+            Some(token) if token.token_type == TokenType::Semicolon => {
+                self.advance();
+                Ok((expr, span))
+            }
+            Some(token) if token.token_type != TokenType::Eof && token.line == last_line => {
+                Err(ParseError::unexpected(token, &[TokenType::Semicolon]))
+            }
+            _ => Ok((expr, span)),
+        }
+    }
+
+    /// Skips past the rest of the expression that failed on `line`, up to the
+    /// next `;` or line break.
+    fn synchronize(&mut self, line: usize) {
+        while let Some(token) = self.peek() {
+            match token.token_type {
+                TokenType::Eof => return,
+                TokenType::Semicolon => {
+                    self.advance();
+                    return;
+                }
+                _ if token.line > line => return,
+                _ => self.advance(),
+            }
+        }
+    }
+
+    /// Returns the token about to be consumed. Past the end of the tokens this
+    /// keeps returning the last one, which is normally `Eof`.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.current).or_else(|| self.tokens.last())
+    }
+
+    fn advance(&mut self) {
+        self.current += 1;
+    }
+
+    fn check(&self, token_type: &TokenType) -> bool {
+        self.peek()
+            .is_some_and(|token| token.token_type == *token_type)
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        match self.peek() {
+            Some(token) if token.token_type != TokenType::Eof => ParseError::at(token, message),
+            _ => self.unexpected_eof(message),
+        }
+    }
+
+    /// An error for running out of tokens where `message` says what should
+    /// have come next.
+    fn unexpected_eof(&self, message: &str) -> ParseError {
+        let last = self.tokens[..self.current.min(self.tokens.len())]
+            .iter()
+            .rev()
+            .find(|token| token.token_type != TokenType::Eof)
+            .or(self.tokens.last());
+        let (line, column) = last.map_or((1, 1), |token| (token.line, token.column));
+
+        ParseError {
+            kind: ParseErrorKind::UnexpectedEof,
+            message: format!("unexpected end of input, {message}"),
+            line,
+            column,
+            expected: Vec::new(),
+            found: TokenType::Eof,
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.expression_bp(0)
+    }
+
+    /// Parses an expression whose infix operators all bind at least as
+    /// tightly as `min_bp`.
+    fn expression_bp(&mut self, min_bp: u8) -> Result<Expr, ParseError> {
+        let mut lhs = self.prefix()?;
+
+        while let Some(token) = self.peek().cloned() {
+            let Some((left_bp, right_bp)) = binding_power(&token.token_type) else {
+                break;
+            };
+
+            if left_bp < min_bp || self.starts_new_line(&token) {
+                break;
+            }
+
+            self.advance();
+
+            lhs = match &token.token_type {
+                TokenType::Die(_) => Expr::Dice {
+                    count: Box::new(lhs),
+                    sides: Box::new(self.operand("expected the number of sides after 'd'")?),
+                },
+                TokenType::Keep(_) => match self.keep_drop_kind(true) {
+                    Some(kind) => Expr::KeepDrop {
+                        inner: Box::new(lhs),
+                        kind,
+                        count: Box::new(self.operand("expected the number of dice to keep")?),
+                    },
+                    None => Expr::Keep {
+                        pool: Box::new(lhs),
+                        count: Box::new(self.operand("expected the number of dice to keep")?),
+                    },
+                },
+                TokenType::KeepHighest
+                | TokenType::KeepLowest
+                | TokenType::DropHighest
+                | TokenType::DropLowest => {
+                    let n = self.operand(&format!(
+                        "expected the number of dice after '{}'",
+                        token.token_type
+                    ))?;
+
+                    keep_drop(lhs, &token.token_type, n)
+                }
+                TokenType::Dot => self.method(lhs)?,
+                TokenType::Bang | TokenType::Explode => Expr::Explode {
+                    inner: Box::new(lhs),
+                    threshold: self.on_clause()?,
+                    depth: DEFAULT_EXPLOSION_DEPTH,
+                },
+                TokenType::Reroll(_) => Expr::Reroll {
+                    inner: Box::new(lhs),
+                    strategy: self.reroll_strategy(),
+                    condition: self.condition()?,
+                },
+                TokenType::Drop => match self.keep_drop_kind(false) {
+                    Some(kind) => Expr::KeepDrop {
+                        inner: Box::new(lhs),
+                        kind,
+                        count: Box::new(self.operand("expected the number of dice to drop")?),
+                    },
+                    None => Expr::Drop {
+                        pool: Box::new(lhs),
+                        count: Box::new(self.operand("expected the number of dice to drop")?),
+                    },
+                },
+                token_type => {
+                    let Some(op) = binary_op(token_type) else {
+                        unreachable!("'{token_type}' has a binding power but no operator");
+                    };
+
+                    let rhs = Box::new(self.expression_bp(right_bp)?);
+
+                    if op.is_comparison() {
+                        Expr::Comparison {
+                            op,
+                            lhs: Box::new(lhs),
+                            rhs,
+                        }
+                    } else {
+                        Expr::BinaryOp {
+                            left: Box::new(lhs),
+                            op,
+                            right: rhs,
+                        }
+                    }
+                }
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    /// Whether `token` begins a new line where it's more likely the start of
+    /// the next expression than an operator carrying this one on.
+    fn starts_new_line(&self, token: &Token) -> bool {
+        let starts_expression = matches!(
+            token.token_type,
+            TokenType::Die(_) | TokenType::Minus | TokenType::Explode
+        );
+
+        starts_expression
+            && self.depth == 0
+            && self.current > 0
+            && token.line > self.tokens[self.current - 1].line
+    }
+
+    fn prefix(&mut self) -> Result<Expr, ParseError> {
+        match self.peek().map(|token| &token.token_type) {
+            Some(TokenType::Minus) => {
+                self.advance();
+                let operand = self.expression_bp(NEGATION_BP)?;
+
+                Ok(Expr::UnaryOp {
+                    op: UnaryOp::Neg,
+                    operand: Box::new(operand),
+                })
+            }
+            // A die with no count, like `d20`, rolls a single die.
+            Some(TokenType::Die(_)) => {
+                self.advance();
+                let sides = self.operand("expected the number of sides after 'd'")?;
+
+                Ok(Expr::Dice {
+                    count: Box::new(Expr::Int(1)),
+                    sides: Box::new(sides),
+                })
+            }
+            Some(TokenType::Max | TokenType::Min) => {
+                let token = self.peek().cloned().expect("just checked");
+                let highest = self.check(&TokenType::Max);
+                self.advance();
+                let args = self.arguments()?;
+
+                if args.is_empty() {
+                    return Err(ParseError::at(
+                        &token,
+                        format!("expected at least one argument to '{}'", token.token_type),
+                    ));
+                }
+
+                Ok(if highest {
+                    Expr::Max(args)
+                } else {
+                    Expr::Min(args)
+                })
+            }
+            // `explode(2d6)` is `2d6!`, and `explode(2d6, 5)` explodes on a 5
+            // or more.
+            Some(TokenType::Explode) => {
+                let token = self.peek().cloned().expect("just checked");
+                self.advance();
+                let mut args = self.arguments()?.into_iter();
+
+                let (Some(inner), threshold, None) = (args.next(), args.next(), args.next()) else {
+                    return Err(ParseError::at(
+                        &token,
+                        "expected one or two arguments to 'explode'",
+                    ));
+                };
+
+                Ok(Expr::Explode {
+                    inner: Box::new(inner),
+                    threshold: threshold.map(|value| Condition {
+                        op: BinaryOp::Ge,
+                        value: Box::new(value),
+                    }),
+                    depth: DEFAULT_EXPLOSION_DEPTH,
+                })
+            }
+            _ => self.primary(),
+        }
+    }
+
+    /// Parses a parenthesized, comma-separated list of expressions, as in
+    /// `max(d6, d8)`. The list may be empty, as in `.explode()`.
+    fn arguments(&mut self) -> Result<Vec<Expr>, ParseError> {
+        self.expect(&TokenType::LeftParen, "expected '('")?;
+        self.depth += 1;
+        let mut args = Vec::new();
+
+        if !self.check(&TokenType::RightParen) {
+            args.push(self.expression()?);
+
+            while self.check(&TokenType::Comma) {
+                self.advance();
+                args.push(self.expression()?);
+            }
+        }
+
+        self.depth -= 1;
+        self.expect(&TokenType::RightParen, "expected ')'")?;
+        Ok(args)
+    }
+
+    /// Parses the method call after the `.` in a chain like
+    /// `4d6.keep(3).reroll(1)` and applies it to `lhs`.
+    ///
+    /// Each method is another way to write a modifier, and parses to the same
+    /// tree: `.keep(3)` is `keep 3`, `.kh(3)` is `kh3`, `.reroll(1)` is
+    /// `reroll once == 1`, and `.explode(5)` is `explode on >= 5`. A reroll or
+    /// explode after a keep or drop applies to the dice it picks from, so
+    /// `4d6.keep(3).reroll(1)` is `4d6 rr == 1 k3`.
+    fn method(&mut self, lhs: Expr) -> Result<Expr, ParseError> {
+        let token = match self.peek().cloned() {
+            Some(token) if is_method(&token.token_type) => token,
+            _ => return Err(self.error("expected a method like 'keep' after '.'")),
+        };
+
+        self.advance();
+        let mut args = self.arguments()?.into_iter();
+        let (first, second) = (args.next(), args.next());
+
+        let expr = match (&token.token_type, first, second) {
+            (TokenType::Keep(_), Some(count), None) => Expr::Keep {
+                pool: Box::new(lhs),
+                count: Box::new(count),
+            },
+            (TokenType::Drop, Some(count), None) => Expr::Drop {
+                pool: Box::new(lhs),
+                count: Box::new(count),
+            },
+            (
+                token_type @ (TokenType::KeepHighest
+                | TokenType::KeepLowest
+                | TokenType::DropHighest
+                | TokenType::DropLowest),
+                Some(count),
+                None,
+            ) => keep_drop(lhs, token_type, count),
+            (TokenType::Reroll(_), Some(face), None) => under_keep(lhs, |dice| Expr::Reroll {
+                inner: Box::new(dice),
+                condition: Condition {
+                    op: BinaryOp::Eq,
+                    value: Box::new(face),
+                },
+                strategy: RerollStrategy::Once,
+            }),
+            (TokenType::Explode, threshold, None) => under_keep(lhs, |dice| Expr::Explode {
+                inner: Box::new(dice),
+                threshold: threshold.map(|value| Condition {
+                    op: BinaryOp::Ge,
+                    value: Box::new(value),
+                }),
+                depth: DEFAULT_EXPLOSION_DEPTH,
+            }),
+            (token_type, ..) => {
+                let expected = match token_type {
+                    TokenType::Explode => "at most one argument",
+                    _ => "one argument",
+                };
+
+                return Err(ParseError::at(
+                    &token,
+                    format!("expected {expected} to '{token_type}'"),
+                ));
+            }
+        };
+
+        Ok(expr)
+    }
+
+    /// Parses the right-hand side of a dice operator like `d` or `k`, which is
+    /// either a number or a parenthesized expression like `d(4 + 2)`.
+    fn operand(&mut self, message: &str) -> Result<Expr, ParseError> {
+        match self.peek().map(|token| &token.token_type) {
+            Some(TokenType::Int(_) | TokenType::LeftParen) => self.primary(),
+            _ => Err(self.error(message)),
+        }
+    }
+
+    /// Parses an optional `on` followed by a condition, as in `explode on >= 5`.
+    fn on_clause(&mut self) -> Result<Option<Condition>, ParseError> {
+        if self.check(&TokenType::On) {
+            self.advance();
+            return self.condition().map(Some);
+        }
+
+        Ok(None)
+    }
+
+    /// Parses the optional `high` or `low` after `keep`, or after `drop` if
+    /// `keep` is false.
+    fn keep_drop_kind(&mut self, keep: bool) -> Option<KeepDropKind> {
+        let kind = match (self.peek().map(|token| &token.token_type), keep) {
+            (Some(TokenType::High), true) => KeepDropKind::KeepHighest,
+            (Some(TokenType::Low), true) => KeepDropKind::KeepLowest,
+            (Some(TokenType::High), false) => KeepDropKind::DropHighest,
+            (Some(TokenType::Low), false) => KeepDropKind::DropLowest,
+            _ => return None,
+        };
+
+        self.advance();
+        Some(kind)
+    }
+
+    /// Parses the optional `once` or `until` after `reroll`.
+    fn reroll_strategy(&mut self) -> RerollStrategy {
+        let strategy = match self.peek().map(|token| &token.token_type) {
+            Some(TokenType::Once) => RerollStrategy::Once,
+            Some(TokenType::Until) => RerollStrategy::Until,
+            _ => return RerollStrategy::default(),
+        };
+
+        self.advance();
+        strategy
+    }
+
+    /// Parses a comparison against each die, like `>= 5` or `== (1 + 2)`.
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let op = match self.peek().and_then(|token| binary_op(&token.token_type)) {
+            Some(op) if op.is_comparison() => op,
+            _ => return Err(self.error("expected a comparison like '>= 5'")),
+        };
+
+        self.advance();
+        let value = self.operand("expected a value to compare against")?;
+
+        Ok(Condition {
+            op,
+            value: Box::new(value),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let token = match self.peek().cloned() {
+            Some(token) => token,
+            None => return Err(self.error("expected an expression")),
+        };
+
+        match token.token_type {
+            TokenType::Int(n) => {
+                self.advance();
+                Ok(Expr::Int(n))
+            }
+            TokenType::LeftParen => {
+                self.advance();
+                self.depth += 1;
+                let inner = self.expression()?;
+                self.depth -= 1;
+                self.expect(&TokenType::RightParen, "expected ')'")?;
+                Ok(Expr::Group(Box::new(inner)))
+            }
+            TokenType::Eof => Err(self.error("expected an expression")),
+            _ => Err(ParseError::unexpected(&token, &[])),
+        }
+    }
+
+    fn expect(&mut self, token_type: &TokenType, message: &str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(token) if token.token_type == *token_type => {
+                self.advance();
+                Ok(())
+            }
+            _ => Err(ParseError {
+                expected: vec![token_type.clone()],
+                ..self.error(message)
+            }),
+        }
+    }
+}
+
+/// How many times an exploding die can chain before the parser's default cap
+/// stops it.
+pub const DEFAULT_EXPLOSION_DEPTH: u32 = 100;
+
+/// Binding power of unary minus. It binds tighter than `*` but looser than
+/// `d`, `k`, and `drop`, so `-2d6` and `-4d6k3` negate the whole roll.
+const NEGATION_BP: u8 = 7;
+
+/// Left and right binding powers of each infix operator, or `None` if the
+/// token isn't one. Higher numbers bind tighter, and a right power above the
+/// left one makes the operator left-associative.
+fn binding_power(token_type: &TokenType) -> Option<(u8, u8)> {
+    match token_type {
+        TokenType::Less
+        | TokenType::LessEqual
+        | TokenType::Greater
+        | TokenType::GreaterEqual
+        | TokenType::EqualEqual
+        | TokenType::BangEqual => Some((1, 2)),
+        TokenType::Plus | TokenType::Minus => Some((3, 4)),
+        TokenType::Star | TokenType::Slash => Some((5, 6)),
+        TokenType::Keep(_)
+        | TokenType::Drop
+        | TokenType::KeepHighest
+        | TokenType::KeepLowest
+        | TokenType::DropHighest
+        | TokenType::DropLowest => Some((7, 8)),
+        TokenType::Die(_) => Some((9, 10)),
+        // Postfix, so only the left power matters.
+        TokenType::Bang | TokenType::Explode | TokenType::Reroll(_) | TokenType::Dot => {
+            Some((11, 12))
+        }
+        _ => None,
+    }
+}
+
+/// Applies a `kh`, `kl`, `dh`, or `dl` of `count` dice to `lhs`. Right after
+/// plain dice this is a pool modifier, and anywhere else it picks among the
+/// dice of `lhs`.
+fn keep_drop(lhs: Expr, token_type: &TokenType, count: Expr) -> Expr {
+    let n = Box::new(count);
+
+    match lhs {
+        Expr::Dice { count, sides } => Expr::DicePool {
+            count,
+            sides,
+            modifier: match token_type {
+                TokenType::KeepHighest => PoolModifier::KeepHighest(n),
+                TokenType::KeepLowest => PoolModifier::KeepLowest(n),
+                TokenType::DropHighest => PoolModifier::DropHighest(n),
+                _ => PoolModifier::DropLowest(n),
+            },
+        },
+        inner => Expr::KeepDrop {
+            inner: Box::new(inner),
+            kind: match token_type {
+                TokenType::KeepHighest => KeepDropKind::KeepHighest,
+                TokenType::KeepLowest => KeepDropKind::KeepLowest,
+                TokenType::DropHighest => KeepDropKind::DropHighest,
+                _ => KeepDropKind::DropLowest,
+            },
+            count: n,
+        },
+    }
+}
+
+/// Applies `modify` to the dice that `lhs` keeps or drops from, or to all of
+/// `lhs` if it doesn't keep or drop any.
+fn under_keep(lhs: Expr, modify: impl FnOnce(Expr) -> Expr) -> Expr {
+    match lhs {
+        Expr::Keep { pool, count } => Expr::Keep {
+            pool: Box::new(under_keep(*pool, modify)),
+            count,
+        },
+        Expr::Drop { pool, count } => Expr::Drop {
+            pool: Box::new(under_keep(*pool, modify)),
+            count,
+        },
+        Expr::KeepDrop { inner, kind, count } => Expr::KeepDrop {
+            inner: Box::new(under_keep(*inner, modify)),
+            kind,
+            count,
+        },
+        Expr::DicePool {
+            count,
+            sides,
+            modifier,
+        } => {
+            let inner = Box::new(modify(Expr::Dice { count, sides }));
+            let (kind, count) = match modifier {
+                PoolModifier::KeepHighest(n) => (KeepDropKind::KeepHighest, n),
+                PoolModifier::KeepLowest(n) => (KeepDropKind::KeepLowest, n),
+                PoolModifier::DropHighest(n) => (KeepDropKind::DropHighest, n),
+                PoolModifier::DropLowest(n) => (KeepDropKind::DropLowest, n),
+                PoolModifier::None => return *inner,
+            };
+
+            Expr::KeepDrop { inner, kind, count }
+        }
+        lhs => modify(lhs),
+    }
+}
+
+/// Whether `token_type` names a method that can follow a `.`.
+fn is_method(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Keep(_)
+            | TokenType::Drop
+            | TokenType::KeepHighest
+            | TokenType::KeepLowest
+            | TokenType::DropHighest
+            | TokenType::DropLowest
+            | TokenType::Reroll(_)
+            | TokenType::Explode
+    )
+}
+
+fn binary_op(token_type: &TokenType) -> Option<BinaryOp> {
+    match token_type {
+        TokenType::Plus => Some(BinaryOp::Add),
+        TokenType::Minus => Some(BinaryOp::Sub),
+        TokenType::Star => Some(BinaryOp::Mul),
+        TokenType::Slash => Some(BinaryOp::Div),
+        TokenType::Less => Some(BinaryOp::Lt),
+        TokenType::LessEqual => Some(BinaryOp::Le),
+        TokenType::Greater => Some(BinaryOp::Gt),
+        TokenType::GreaterEqual => Some(BinaryOp::Ge),
+        TokenType::EqualEqual => Some(BinaryOp::Eq),
+        TokenType::BangEqual => Some(BinaryOp::Ne),
+        _ => None,
+    }
+}
+
+/// Parses a whole token stream into a single expression. See
+/// [`Parser::parse`].
+pub fn parse(tokens: &[Token]) -> Result<Expr, ParseError> {
+    Parser::new(tokens.to_vec()).parse()
+}
+
+/// Parses a whole token stream into a sequence of expressions. See
+/// [`Parser::parse_all`].
+pub fn parse_all(tokens: &[Token]) -> Result<Vec<Expr>, Vec<ParseError>> {
+    Parser::new(tokens.to_vec()).parse_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::{tokenize_strict, Span};
+
+    fn parse_str(input: &str) -> Result<Expr, ParseError> {
+        parse(&tokenize_strict(input).unwrap())
+    }
+
+    fn int(n: i32) -> Box<Expr> {
+        Box::new(Expr::Int(n))
+    }
+
+    #[test]
+    fn test_precedence() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Add,
+            left: int(1),
+            right: Box::new(Expr::BinaryOp {
+                op: BinaryOp::Mul,
+                left: int(2),
+                right: int(3),
+            }),
+        };
+
+        assert_eq!(Ok(expected), parse_str("1 + 2 * 3"));
+    }
+
+    #[test]
+    fn test_grouping() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Mul,
+            left: Box::new(Expr::Group(Box::new(Expr::BinaryOp {
+                op: BinaryOp::Add,
+                left: int(1),
+                right: int(2),
+            }))),
+            right: int(3),
+        };
+
+        assert_eq!(Ok(expected), parse_str("(1 + 2) * 3"));
+    }
+
+    #[test]
+    fn test_left_associativity() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Sub,
+            left: Box::new(Expr::BinaryOp {
+                op: BinaryOp::Sub,
+                left: int(8),
+                right: int(4),
+            }),
+            right: int(2),
+        };
+
+        assert_eq!(Ok(expected), parse_str("8 - 4 - 2"));
+    }
+
+    #[test]
+    fn test_unary_minus() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Sub,
+            left: int(3),
+            right: Box::new(Expr::UnaryOp {
+                op: UnaryOp::Neg,
+                operand: int(2),
+            }),
+        };
+
+        assert_eq!(Ok(expected), parse_str("3 - -2"));
+    }
+
+    #[test]
+    fn test_missing_right_paren() {
+        let expected = ParseError {
+            kind: ParseErrorKind::UnexpectedEof,
+            message: String::from("unexpected end of input, expected ')'"),
+            line: 1,
+            column: 6,
+            expected: vec![TokenType::RightParen],
+            found: TokenType::Eof,
+        };
+
+        assert_eq!(Err(expected), parse_str("(1 + 2"));
+    }
+
+    #[test]
+    fn test_trailing_tokens() {
+        let expected = ParseError {
+            kind: ParseErrorKind::UnexpectedToken,
+            message: String::from("unexpected ')'"),
+            line: 1,
+            column: 3,
+            expected: Vec::new(),
+            found: TokenType::RightParen,
+        };
+
+        assert_eq!(Err(expected), parse_str("1 )"));
+    }
+
+    #[test]
+    fn test_dice() {
+        let expected = Expr::Dice {
+            count: int(2),
+            sides: int(6),
+        };
+
+        assert_eq!(Ok(expected), parse_str("2d6"));
+    }
+
+    #[test]
+    fn test_dice_count_defaults_to_one() {
+        let expected = Expr::Dice {
+            count: int(1),
+            sides: int(20),
+        };
+
+        assert_eq!(Ok(expected), parse_str("d20"));
+        assert_eq!(parse_str("1d20"), parse_str("d20"));
+    }
+
+    #[test]
+    fn test_dice_with_expression_sides() {
+        let expected = Expr::Dice {
+            count: int(3),
+            sides: Box::new(Expr::Group(Box::new(Expr::BinaryOp {
+                op: BinaryOp::Add,
+                left: int(2),
+                right: int(4),
+            }))),
+        };
+
+        assert_eq!(Ok(expected), parse_str("3d(2+4)"));
+    }
+
+    #[test]
+    fn test_dice_bind_tighter_than_arithmetic() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Mul,
+            left: Box::new(Expr::Dice {
+                count: int(2),
+                sides: int(6),
+            }),
+            right: int(3),
+        };
+
+        assert_eq!(Ok(expected), parse_str("2d6 * 3"));
+    }
+
+    #[test]
+    fn test_die_without_sides() {
+        let expected = ParseError {
+            kind: ParseErrorKind::UnexpectedEof,
+            message: String::from(
+                "unexpected end of input, expected the number of sides after 'd'",
+            ),
+            line: 1,
+            column: 1,
+            expected: Vec::new(),
+            found: TokenType::Eof,
+        };
+
+        assert_eq!(Err(expected), parse_str("d"));
+    }
+
+    #[test]
+    fn test_operator_binding() {
+        let dice = Expr::Dice {
+            count: int(2),
+            sides: int(6),
+        };
+        let product = Expr::BinaryOp {
+            op: BinaryOp::Mul,
+            left: Box::new(dice),
+            right: int(3),
+        };
+        let sum = Expr::BinaryOp {
+            op: BinaryOp::Add,
+            left: int(1),
+            right: Box::new(product),
+        };
+        let expected = Expr::Comparison {
+            op: BinaryOp::Lt,
+            lhs: Box::new(sum),
+            rhs: int(20),
+        };
+
+        assert_eq!(Ok(expected), parse_str("1 + 2d6 * 3 < 20"));
+    }
+
+    #[test]
+    fn test_negation_binds_looser_than_dice() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Mul,
+            left: Box::new(Expr::UnaryOp {
+                op: UnaryOp::Neg,
+                operand: Box::new(Expr::Dice {
+                    count: int(2),
+                    sides: int(6),
+                }),
+            }),
+            right: int(2),
+        };
+
+        assert_eq!(Ok(expected), parse_str("-2d6 * 2"));
+    }
+
+    #[test]
+    fn test_negative_literals() {
+        let negative = |operand| Expr::UnaryOp {
+            op: UnaryOp::Neg,
+            operand,
+        };
+
+        assert_eq!(Ok(negative(int(5))), parse_str("-5"));
+        assert_eq!(Ok(negative(Box::new(negative(int(5))))), parse_str("--5"));
+        // Parentheses are needed to negate the count rather than the roll.
+        assert_eq!(
+            Ok(Expr::Dice {
+                count: Box::new(Expr::Group(Box::new(negative(int(2))))),
+                sides: int(6),
+            }),
+            parse_str("(-2)d6")
+        );
+    }
+
+    #[test]
+    fn test_keep() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Add,
+            left: Box::new(Expr::Keep {
+                pool: Box::new(Expr::Dice {
+                    count: int(4),
+                    sides: int(6),
+                }),
+                count: int(3),
+            }),
+            right: int(1),
+        };
+
+        assert_eq!(Ok(expected), parse_str("4d6k3 + 1"));
+        assert_eq!(parse_str("4d6k3"), parse_str("4d6 keep 3"));
+    }
+
+    #[test]
+    fn test_keep_without_count() {
+        let expected = ParseError {
+            kind: ParseErrorKind::UnexpectedEof,
+            message: String::from("unexpected end of input, expected the number of dice to keep"),
+            line: 1,
+            column: 4,
+            expected: Vec::new(),
+            found: TokenType::Eof,
+        };
+
+        assert_eq!(Err(expected), parse_str("4d6k"));
+    }
+
+    #[test]
+    fn test_drop() {
+        let expected = Expr::Drop {
+            pool: Box::new(Expr::Dice {
+                count: int(4),
+                sides: int(6),
+            }),
+            count: int(1),
+        };
+
+        assert_eq!(Ok(expected), parse_str("4d6 drop 1"));
+    }
+
+    #[test]
+    fn test_explode() {
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Add,
+            left: Box::new(Expr::Explode {
+                inner: Box::new(Expr::Dice {
+                    count: int(2),
+                    sides: int(6),
+                }),
+                threshold: None,
+                depth: DEFAULT_EXPLOSION_DEPTH,
+            }),
+            right: int(1),
+        };
+
+        assert_eq!(Ok(expected), parse_str("2d6! + 1"));
+        assert_eq!(parse_str("2d6!"), parse_str("2d6 explode"));
+    }
+
+    #[test]
+    fn test_parser() {
+        let mut parser = Parser::new(tokenize_strict("2d6 + 3").unwrap());
+
+        let expected = Expr::BinaryOp {
+            op: BinaryOp::Add,
+            left: Box::new(Expr::Dice {
+                count: int(2),
+                sides: int(6),
+            }),
+            right: int(3),
+        };
+
+        assert_eq!(Ok(expected), parser.parse());
+    }
+
+    #[test]
+    fn test_dice_pools() {
+        let pool = |modifier| {
+            Ok(Expr::DicePool {
+                count: int(4),
+                sides: int(6),
+                modifier,
+            })
+        };
+
+        assert_eq!(pool(PoolModifier::KeepHighest(int(3))), parse_str("4d6kh3"));
+        assert_eq!(pool(PoolModifier::KeepLowest(int(1))), parse_str("4d6kl1"));
+        assert_eq!(pool(PoolModifier::DropHighest(int(2))), parse_str("4d6dh2"));
+        assert_eq!(pool(PoolModifier::DropLowest(int(1))), parse_str("4d6dl1"));
+    }
+
+    #[test]
+    fn test_dice_pool_count_can_be_grouped() {
+        let expected = Expr::DicePool {
+            count: int(4),
+            sides: int(6),
+            modifier: PoolModifier::KeepHighest(Box::new(Expr::Group(Box::new(Expr::BinaryOp {
+                left: int(1),
+                op: BinaryOp::Add,
+                right: int(2),
+            })))),
+        };
+
+        assert_eq!(Ok(expected), parse_str("4d6kh(1 + 2)"));
+    }
+
+    #[test]
+    fn test_keep_drop() {
+        let keep_drop = |kind, count| {
+            Ok(Expr::KeepDrop {
+                inner: Box::new(Expr::Group(Box::new(Expr::Dice {
+                    count: int(4),
+                    sides: int(6),
+                }))),
+                kind,
+                count: int(count),
+            })
+        };
+
+        for (long, short, kind) in [
+            ("(4d6) keep high 3", "(4d6)kh3", KeepDropKind::KeepHighest),
+            ("(4d6) keep low 3", "(4d6)kl3", KeepDropKind::KeepLowest),
+            ("(4d6) drop high 1", "(4d6)dh1", KeepDropKind::DropHighest),
+            ("(4d6) drop low 1", "(4d6)dl1", KeepDropKind::DropLowest),
+        ] {
+            let count = if long.contains("keep") { 3 } else { 1 };
+
+            assert_eq!(keep_drop(kind, count), parse_str(long));
+            assert_eq!(keep_drop(kind, count), parse_str(short));
+        }
+    }
+
+    #[test]
+    fn test_keep_high_on_plain_dice() {
+        let Ok(Expr::KeepDrop { inner, .. }) = parse_str("4d6 keep high 3") else {
+            panic!("expected a keep/drop");
+        };
+
+        assert!(matches!(*inner, Expr::Dice { .. }));
+        assert!(matches!(parse_str("4d6 keep 3"), Ok(Expr::Keep { .. })));
+    }
+
+    #[test]
+    fn test_keep_drop_needs_count() {
+        let expected = ParseError {
+            kind: ParseErrorKind::UnexpectedEof,
+            message: String::from(
+                "unexpected end of input, expected the number of dice after 'kh'",
+            ),
+            line: 1,
+            column: 6,
+            expected: Vec::new(),
+            found: TokenType::Eof,
+        };
+
+        assert_eq!(Err(expected), parse_str("(4d6)kh"));
+        assert_eq!(
+            Err(ParseError {
+                kind: ParseErrorKind::UnexpectedEof,
+                message: String::from(
+                    "unexpected end of input, expected the number of dice to keep"
+                ),
+                line: 1,
+                column: 12,
+                expected: Vec::new(),
+                found: TokenType::Eof,
+            }),
+            parse_str("(4d6) keep high")
+        );
+    }
+
+    #[test]
+    fn test_explode_on_threshold() {
+        let expected = Expr::Explode {
+            inner: Box::new(Expr::Dice {
+                count: int(2),
+                sides: int(6),
+            }),
+            threshold: Some(Condition {
+                op: BinaryOp::Ge,
+                value: int(5),
+            }),
+            depth: DEFAULT_EXPLOSION_DEPTH,
+        };
+
+        assert_eq!(Ok(expected), parse_str("2d6 explode on >= 5"));
+        assert_eq!(parse_str("2d6! on >= 5"), parse_str("2d6 explode on >= 5"));
+    }
+
+    #[test]
+    fn test_explode_on_without_comparison() {
+        let expected = ParseError {
+            kind: ParseErrorKind::UnexpectedToken,
+            message: String::from("expected a comparison like '>= 5'"),
+            line: 1,
+            column: 16,
+            expected: Vec::new(),
+            found: TokenType::Int(5),
+        };
+
+        assert_eq!(Err(expected), parse_str("2d6 explode on 5"));
+    }
+
+    #[test]
+    fn test_reroll() {
+        let dice = Box::new(Expr::Dice {
+            count: int(3),
+            sides: int(6),
+        });
+
+        assert_eq!(
+            Ok(Expr::Reroll {
+                inner: dice.clone(),
+                condition: Condition {
+                    op: BinaryOp::Le,
+                    value: int(2),
+                },
+                strategy: RerollStrategy::Once,
+            }),
+            parse_str("3d6 reroll once <= 2")
+        );
+        assert_eq!(
+            Ok(Expr::Reroll {
+                inner: dice,
+                condition: Condition {
+                    op: BinaryOp::Gt,
+                    value: int(1),
+                },
+                strategy: RerollStrategy::Until,
+            }),
+            parse_str("3d6 reroll until > 1")
+        );
+    }
+
+    #[test]
+    fn test_reroll_defaults_to_once() {
+        assert_eq!(parse_str("3d6 reroll once == 1"), parse_str("3d6 rr == 1"));
+    }
+
+    #[test]
+    fn test_reroll_binds_before_keep() {
+        let Ok(Expr::Keep { pool, .. }) = parse_str("4d6 rr < 2 k3") else {
+            panic!("expected a keep");
+        };
+
+        assert!(matches!(*pool, Expr::Reroll { .. }));
+    }
+
+    #[test]
+    fn test_reroll_needs_condition() {
+        let expected = ParseError {
+            kind: ParseErrorKind::UnexpectedEof,
+            message: String::from("unexpected end of input, expected a comparison like '>= 5'"),
+            line: 1,
+            column: 5,
+            expected: Vec::new(),
+            found: TokenType::Eof,
+        };
+
+        assert_eq!(Err(expected.clone()), parse_str("3d6 reroll"));
+        assert_eq!(
+            Err(ParseError {
+                column: 12,
+                ..expected
+            }),
+            parse_str("3d6 reroll once")
+        );
+    }
+
+    #[test]
+    fn test_unexpected() {
+        let token = Token::new(TokenType::Int(3), 2, 5, Span::new(9, 10));
+
+        assert_eq!(
+            ParseError {
+                kind: ParseErrorKind::UnexpectedToken,
+                message: String::from("expected ';' or ')', found '3'"),
+                line: 2,
+                column: 5,
+                expected: vec![TokenType::Semicolon, TokenType::RightParen],
+                found: TokenType::Int(3),
+            },
+            ParseError::unexpected(&token, &[TokenType::Semicolon, TokenType::RightParen])
+        );
+        assert_eq!(
+            "unexpected '3'",
+            ParseError::unexpected(&token, &[]).message
+        );
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let tokens = tokenize_strict("d6; 2d6\n4d6k3\n  + 1;").unwrap();
+        let exprs = Parser::new(tokens).parse_all().unwrap();
+
+        assert_eq!(3, exprs.len());
+        assert_eq!(Ok(exprs[1].clone()), parse_str("2d6"));
+        assert_eq!(Ok(exprs[2].clone()), parse_str("4d6k3 + 1"));
+    }
+
+    #[test]
+    fn test_parse_all_with_spans() {
+        let source = "d6; 2d6\n4d6k3\n  + 1;";
+        let exprs = Parser::new(tokenize_strict(source).unwrap())
+            .parse_all_with_spans()
+            .unwrap();
+
+        let texts: Vec<&str> = exprs.iter().map(|(_, span)| span.text(source)).collect();
+
+        assert_eq!(vec!["d6", "2d6", "4d6k3\n  + 1"], texts);
+    }
+
+    #[test]
+    fn test_parse_all_line_breaks() {
+        let tokens = tokenize_strict("2d6\nd20\n2d6\n-1\n(2d6\n- 1)\nmax(d6,\nd8)").unwrap();
+        let exprs = Parser::new(tokens).parse_all().unwrap();
+
+        let expected: Vec<Expr> = ["2d6", "d20", "2d6", "-1", "(2d6 - 1)", "max(d6, d8)"]
+            .into_iter()
+            .map(|input| parse_str(input).unwrap())
+            .collect();
+
+        assert_eq!(expected, exprs);
+    }
+
+    #[test]
+    fn test_parse_all_collects_errors() {
+        let tokens = tokenize_strict("1 +; 2d6\n(3 4\n5 )\n4d6k").unwrap();
+        let errors = Parser::new(tokens).parse_all().unwrap_err();
+
+        let positions: Vec<(usize, usize, &str)> = errors
+            .iter()
+            .map(|error| (error.line, error.column, error.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (1, 4, "unexpected ';'"),
+                (2, 4, "expected ')'"),
+                (3, 3, "expected ';', found ')'"),
+                (
+                    4,
+                    4,
+                    "unexpected end of input, expected the number of dice to keep"
+                ),
+            ],
+            positions
+        );
+        assert_eq!(vec![TokenType::RightParen], errors[1].expected);
+        assert_eq!(TokenType::Int(4), errors[1].found);
+        assert_eq!(vec![TokenType::Semicolon], errors[2].expected);
+    }
+
+    #[test]
+    fn test_comparisons() {
+        for (input, op) in [
+            ("d20 < 5", BinaryOp::Lt),
+            ("d20 <= 5", BinaryOp::Le),
+            ("d20 > 5", BinaryOp::Gt),
+            ("d20 >= 5", BinaryOp::Ge),
+            ("d20 == 5", BinaryOp::Eq),
+            ("d20 != 5", BinaryOp::Ne),
+        ] {
+            let expected = Expr::Comparison {
+                op,
+                lhs: Box::new(Expr::Dice {
+                    count: int(1),
+                    sides: int(20),
+                }),
+                rhs: int(5),
+            };
+
+            assert_eq!(Ok(expected), parse_str(input));
+        }
+    }
+
+    #[test]
+    fn test_max_and_min() {
+        let d6 = Expr::Dice {
+            count: int(1),
+            sides: int(6),
+        };
+        let d8 = Expr::Dice {
+            count: int(1),
+            sides: int(8),
+        };
+
+        assert_eq!(
+            Ok(Expr::Max(vec![d6.clone(), d8])),
+            parse_str("max(d6, d8)")
+        );
+
+        assert_eq!(
+            Ok(Expr::Max(vec![d6.clone(), d6.clone()])),
+            parse_str("max(d6, d6)")
+        );
+        assert_eq!(
+            Ok(Expr::BinaryOp {
+                left: Box::new(Expr::Min(vec![d6, Expr::Int(3)])),
+                op: BinaryOp::Add,
+                right: int(1),
+            }),
+            parse_str("min(d6, 3) + 1")
+        );
+    }
+
+    #[test]
+    fn test_explode_call() {
+        assert_eq!(parse_str("2d6!"), parse_str("explode(2d6)"));
+        assert_eq!(
+            parse_str("2d6 explode on >= 5"),
+            parse_str("explode(2d6, 5)")
+        );
+        assert_eq!(parse_str("max(d6!, d8)"), parse_str("max(explode(d6), d8)"));
+
+        let error = parse_str("1 + explode(d6, 5, 6)").unwrap_err();
+
+        assert_eq!("expected one or two arguments to 'explode'", error.message);
+        assert_eq!(5, error.column);
+    }
+
+    #[test]
+    fn test_max_needs_arguments() {
+        assert_eq!(
+            "expected at least one argument to 'min'",
+            parse_str("min()").unwrap_err().message
+        );
+
+        let error = parse_str("max d6").unwrap_err();
+
+        assert_eq!("expected '('", error.message);
+        assert_eq!(vec![TokenType::LeftParen], error.expected);
+        assert_eq!(
+            "unexpected end of input, expected ')'",
+            parse_str("max(d6, d6").unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        for (input, column) in [("2 +", 3), ("(1 + 2", 6), ("d", 1), ("", 1)] {
+            let error = parse_str(input).unwrap_err();
+
+            assert_eq!(ParseErrorKind::UnexpectedEof, error.kind, "{input}");
+            assert_eq!((1, column), (error.line, error.column), "{input}");
+            assert!(error.message.starts_with("unexpected end of input"));
+        }
+
+        assert_eq!(
+            ParseErrorKind::UnexpectedToken,
+            parse_str("2 + )").unwrap_err().kind
+        );
+    }
+
+    #[test]
+    fn test_method_chains() {
+        for (chain, infix) in [
+            ("4d6.keep(3)", "4d6 keep 3"),
+            ("4d6.k(3)", "4d6k3"),
+            ("4d6.drop(1)", "4d6 drop 1"),
+            ("4d6.kh(3)", "4d6kh3"),
+            ("(4d6).dl(1)", "(4d6)dl1"),
+            ("4d6.reroll(1)", "4d6 reroll once == 1"),
+            ("2d6.explode()", "2d6!"),
+            ("2d6.explode(5)", "2d6 explode on >= 5"),
+            ("4d6.reroll(1).keep(3)", "4d6 rr == 1 k3"),
+            ("4d6.keep(3).reroll(1)", "4d6 rr == 1 k3"),
+            ("4d6.kh(3).explode()", "4d6! kh3"),
+            ("(4d6k3).reroll(1)", "(4d6k3) reroll once == 1"),
+            ("1 + 4d6.keep(3) * 2", "1 + 4d6k3 * 2"),
+        ] {
+            assert_eq!(parse_str(infix), parse_str(chain), "{chain}");
+        }
+    }
+
+    #[test]
+    fn test_bad_method_chains() {
+        let message = |input| parse_str(input).unwrap_err().message;
+
+        assert_eq!(
+            "expected a method like 'keep' after '.'",
+            message("4d6.max(3)")
+        );
+        assert_eq!("expected one argument to 'keep'", message("4d6.keep()"));
+        assert_eq!("expected one argument to 'kh'", message("4d6.kh(3, 1)"));
+        assert_eq!(
+            "expected at most one argument to 'explode'",
+            message("4d6.explode(5, 6)")
+        );
+        assert_eq!("expected '('", message("4d6.keep 3"));
+    }
+}
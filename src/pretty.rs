@@ -0,0 +1,170 @@
+use crate::{
+    ast::{BinaryOp, Condition, Expr, KeepDropKind, PoolModifier, RerollStrategy, UnaryOp},
+    visitor::ExprVisitor,
+};
+
+impl Expr {
+    /// Renders the tree as a fully parenthesized s-expression, like
+    /// `(+ 1 (* (d 2 6) 3))` for `1 + 2d6 * 3`, to show how the parser grouped
+    /// things. Parentheses from the source are implied by the nesting rather
+    /// than shown.
+    pub fn pretty(&self) -> String {
+        self.accept(&mut PrettyPrinter)
+    }
+}
+
+struct PrettyPrinter;
+
+impl PrettyPrinter {
+    /// `(head arg arg ...)`, with each argument rendered in turn.
+    fn list<'a>(&mut self, head: &str, args: impl IntoIterator<Item = &'a Expr>) -> String {
+        let mut list = format!("({head}");
+
+        for arg in args {
+            list.push(' ');
+            list.push_str(&arg.accept(self));
+        }
+
+        list.push(')');
+        list
+    }
+
+    fn condition(&mut self, condition: &Condition) -> String {
+        self.list(&condition.op.to_string(), [condition.value.as_ref()])
+    }
+}
+
+impl ExprVisitor for PrettyPrinter {
+    type Output = String;
+
+    fn visit_int(&mut self, n: i32) -> String {
+        n.to_string()
+    }
+
+    fn visit_dice(&mut self, count: &Expr, sides: &Expr) -> String {
+        self.list("d", [count, sides])
+    }
+
+    fn visit_dice_pool(&mut self, count: &Expr, sides: &Expr, modifier: &PoolModifier) -> String {
+        let (name, n) = match modifier {
+            PoolModifier::KeepHighest(n) => ("kh", n),
+            PoolModifier::KeepLowest(n) => ("kl", n),
+            PoolModifier::DropHighest(n) => ("dh", n),
+            PoolModifier::DropLowest(n) => ("dl", n),
+            PoolModifier::None => return self.list("d", [count, sides]),
+        };
+
+        format!(
+            "(d {} {} {name} {})",
+            count.accept(self),
+            sides.accept(self),
+            n.accept(self)
+        )
+    }
+
+    fn visit_binary_op(&mut self, left: &Expr, op: BinaryOp, right: &Expr) -> String {
+        self.list(&op.to_string(), [left, right])
+    }
+
+    fn visit_unary_op(&mut self, op: UnaryOp, operand: &Expr) -> String {
+        match op {
+            UnaryOp::Neg => self.list("-", [operand]),
+        }
+    }
+
+    fn visit_comparison(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> String {
+        self.list(&op.to_string(), [lhs, rhs])
+    }
+
+    fn visit_keep(&mut self, pool: &Expr, count: &Expr) -> String {
+        self.list("k", [pool, count])
+    }
+
+    fn visit_drop(&mut self, pool: &Expr, count: &Expr) -> String {
+        self.list("drop", [pool, count])
+    }
+
+    fn visit_keep_drop(&mut self, inner: &Expr, kind: KeepDropKind, count: &Expr) -> String {
+        let name = match kind {
+            KeepDropKind::KeepHighest => "kh",
+            KeepDropKind::KeepLowest => "kl",
+            KeepDropKind::DropHighest => "dh",
+            KeepDropKind::DropLowest => "dl",
+        };
+
+        self.list(name, [inner, count])
+    }
+
+    fn visit_explode(&mut self, inner: &Expr, threshold: Option<&Condition>, _: u32) -> String {
+        match threshold {
+            Some(threshold) => format!("(! {} {})", inner.accept(self), self.condition(threshold)),
+            None => self.list("!", [inner]),
+        }
+    }
+
+    fn visit_reroll(
+        &mut self,
+        inner: &Expr,
+        condition: &Condition,
+        strategy: RerollStrategy,
+    ) -> String {
+        let name = match strategy {
+            RerollStrategy::Once => "reroll-once",
+            RerollStrategy::Until => "reroll-until",
+        };
+
+        format!(
+            "({name} {} {})",
+            inner.accept(self),
+            self.condition(condition)
+        )
+    }
+
+    fn visit_max(&mut self, args: &[Expr]) -> String {
+        self.list("max", args)
+    }
+
+    fn visit_min(&mut self, args: &[Expr]) -> String {
+        self.list("min", args)
+    }
+
+    fn visit_group(&mut self, inner: &Expr) -> String {
+        inner.accept(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, tokenize_strict};
+
+    fn pretty(input: &str) -> String {
+        parse(&tokenize_strict(input).unwrap()).unwrap().pretty()
+    }
+
+    #[test]
+    fn test_precedence_is_visible() {
+        assert_eq!("(+ 1 (* (d 2 6) 3))", pretty("1 + 2d6 * 3"));
+        assert_eq!("(* (+ 1 (d 2 6)) 3)", pretty("(1 + 2d6) * 3"));
+        assert_eq!("(- (- 8 4) 2)", pretty("8 - 4 - 2"));
+        assert_eq!("(- (d 2 6))", pretty("-2d6"));
+        assert_eq!("(>= (+ (d 1 20) 5) 15)", pretty("d20 + 5 >= 15"));
+    }
+
+    #[test]
+    fn test_dice_modifiers() {
+        assert_eq!("(d 4 6 kh 3)", pretty("4d6kh3"));
+        assert_eq!("(kh (d 4 6) 3)", pretty("(4d6)kh3"));
+        assert_eq!("(k (d 4 6) 3)", pretty("4d6k3"));
+        assert_eq!("(drop (d 4 6) 1)", pretty("4d6 drop 1"));
+        assert_eq!("(! (d 2 6))", pretty("2d6!"));
+        assert_eq!("(! (d 2 6) (>= 5))", pretty("2d6 explode on >= 5"));
+        assert_eq!(
+            "(reroll-until (d 3 6) (< 2))",
+            pretty("3d6 reroll until < 2")
+        );
+        assert_eq!(
+            "(max (d 1 6) (min (d 1 8) 3))",
+            pretty("max(d6, min(d8, 3))")
+        );
+    }
+}
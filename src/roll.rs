@@ -0,0 +1,571 @@
+use crate::{
+    ast::{BinaryOp, Condition, Expr, KeepDropKind, PoolModifier, RerollStrategy, UnaryOp},
+    eval::{distribution, EvalError},
+};
+
+/// A source of random numbers for [`roll`].
+pub trait Rng {
+    /// Returns the next 64 random bits.
+    fn next_u64(&mut self) -> u64;
+
+    /// Returns a number from 1 to `sides` inclusive, each equally likely.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sides` is less than 1.
+    fn roll_die(&mut self, sides: i32) -> i32 {
+        assert!(sides >= 1, "a die needs at least one side, not {sides}");
+
+        let sides = sides as u64;
+        // Throw away the top sliver of values that would make some faces
+        // come up more often than others.
+        let limit = u64::MAX - u64::MAX % sides;
+
+        loop {
+            let n = self.next_u64();
+
+            if n < limit {
+                return (n % sides) as i32 + 1;
+            }
+        }
+    }
+}
+
+/// A small, fast xorshift64* generator. Given the same seed it always produces
+/// the same numbers, which makes rolls reproducible. It is not suitable for
+/// anything security-sensitive.
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // An all-zero state would only ever produce zeros.
+        let state = if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        };
+
+        Self { state }
+    }
+}
+
+impl Rng for XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+/// Rolls `expr` once, sampling every die it contains from `rng`.
+///
+/// This follows the same rules as [`distribution`], so over many rolls the
+/// results match it. It fails in the same cases too, but only when the roll it
+/// happens to make does, so `6 / (d2 - 1)` fails about half the time.
+pub fn roll(expr: &Expr, rng: &mut impl Rng) -> Result<i32, EvalError> {
+    let result = match expr {
+        Expr::Int(n) => *n,
+        Expr::Dice { .. } | Expr::Explode { .. } | Expr::Reroll { .. } => {
+            roll_pool(expr, rng)?.into_iter().sum()
+        }
+        Expr::BinaryOp { left, op, right } => {
+            let lhs = roll(left, rng)?;
+            let rhs = roll(right, rng)?;
+
+            if *op == BinaryOp::Div && rhs == 0 {
+                return Err(EvalError::DivisionByZero);
+            }
+
+            op.apply(lhs, rhs).ok_or(EvalError::Overflow)?
+        }
+        Expr::UnaryOp {
+            op: UnaryOp::Neg,
+            operand,
+        } => roll(operand, rng)?
+            .checked_neg()
+            .ok_or(EvalError::Overflow)?,
+        Expr::Comparison { op, lhs, rhs } if lhs.counts_successes() => {
+            let dice = roll_pool(lhs, rng)?;
+            let target = roll(rhs, rng)?;
+
+            dice.into_iter().filter(|&n| op.holds(n, target)).count() as i32
+        }
+        Expr::Comparison { op, lhs, rhs } => i32::from(op.holds(roll(lhs, rng)?, roll(rhs, rng)?)),
+        Expr::DicePool {
+            count,
+            sides,
+            modifier,
+        } => {
+            let count = roll(count, rng)?;
+            let sides = sides_of(roll(sides, rng)?)?;
+            let dice: Vec<i32> = (0..count).map(|_| rng.roll_die(sides)).collect();
+
+            let (keep, highest) = match modifier {
+                PoolModifier::KeepHighest(n) => (roll(n, rng)?, true),
+                PoolModifier::KeepLowest(n) => (roll(n, rng)?, false),
+                PoolModifier::DropHighest(n) => (remaining(&dice, roll(n, rng)?)?, false),
+                PoolModifier::DropLowest(n) => (remaining(&dice, roll(n, rng)?)?, true),
+                PoolModifier::None => (remaining(&dice, 0)?, true),
+            };
+
+            sum_of_kept(dice, keep, highest)
+        }
+        Expr::Keep { pool, count } => {
+            let dice = roll_pool(pool, rng)?;
+            let keep = roll(count, rng)?;
+
+            sum_of_kept(dice, keep, true)
+        }
+        Expr::Drop { pool, count } => {
+            let dice = roll_pool(pool, rng)?;
+            let keep = remaining(&dice, roll(count, rng)?)?;
+
+            sum_of_kept(dice, keep, true)
+        }
+        Expr::KeepDrop { inner, kind, count } => {
+            let dice = roll_pool(inner, rng)?;
+            let n = roll(count, rng)?;
+
+            let (keep, highest) = match kind {
+                KeepDropKind::KeepHighest => (n, true),
+                KeepDropKind::KeepLowest => (n, false),
+                KeepDropKind::DropHighest => (remaining(&dice, n)?, false),
+                KeepDropKind::DropLowest => (remaining(&dice, n)?, true),
+            };
+
+            sum_of_kept(dice, keep, highest)
+        }
+        Expr::Max(args) => roll_all(args, rng)?
+            .into_iter()
+            .max()
+            .ok_or(EvalError::NoArguments)?,
+        Expr::Min(args) => roll_all(args, rng)?
+            .into_iter()
+            .min()
+            .ok_or(EvalError::NoArguments)?,
+        Expr::Group(inner) => roll(inner, rng)?,
+    };
+
+    Ok(result)
+}
+
+fn roll_all(exprs: &[Expr], rng: &mut impl Rng) -> Result<Vec<i32>, EvalError> {
+    exprs.iter().map(|expr| roll(expr, rng)).collect()
+}
+
+/// `sides`, if a die can have that many.
+fn sides_of(sides: i32) -> Result<i32, EvalError> {
+    if sides < 1 {
+        return Err(EvalError::TooFewSides(sides));
+    }
+
+    Ok(sides)
+}
+
+/// How many of `dice` are left after dropping `drop` of them, which may be
+/// negative when dropping more than there are.
+fn remaining(dice: &[i32], drop: i32) -> Result<i32, EvalError> {
+    i32::try_from(dice.len())
+        .ok()
+        .and_then(|len| len.checked_sub(drop))
+        .ok_or(EvalError::Overflow)
+}
+
+/// Rolls each die in `expr` separately, so modifiers like `k` can pick among
+/// them. Anything that isn't a dice roll counts as one die.
+fn roll_pool(expr: &Expr, rng: &mut impl Rng) -> Result<Vec<i32>, EvalError> {
+    let (count, die) = pool(expr, rng)?;
+
+    (0..count).map(|_| die.roll(rng)).collect()
+}
+
+/// One die of a pool, with any explode or reroll modifiers stacked on it. This
+/// mirrors the single-die distributions that [`distribution`] works out, so
+/// `3d6 rr == 1 !` rerolls and then explodes each die in turn.
+enum Die<'a> {
+    /// A fair die with this many sides.
+    Fair(i32),
+    /// Something other than dice, like `(d6 + 1)`, rolled whole.
+    Whole(&'a Expr),
+    /// A die rolled again and added each time it shows a face where
+    /// `face op value` holds, up to `depth` times.
+    Exploded {
+        die: Box<Die<'a>>,
+        op: BinaryOp,
+        value: i32,
+        depth: u32,
+    },
+    /// A die rolled again when it shows a face where `face op value` holds.
+    Rerolled {
+        die: Box<Die<'a>>,
+        op: BinaryOp,
+        value: i32,
+        strategy: RerollStrategy,
+    },
+}
+
+/// How many dice `expr` rolls and what each one is. Counts, sides, and
+/// thresholds are rolled once for the whole pool, as in `distribution`.
+///
+/// A modifier that [`distribution`] would leave alone, like an explosion
+/// where every face or no face explodes, is left out.
+fn pool<'a>(expr: &'a Expr, rng: &mut impl Rng) -> Result<(i32, Die<'a>), EvalError> {
+    let pool = match expr {
+        Expr::Dice { count, sides } => {
+            let count = roll(count, rng)?;
+            let sides = sides_of(roll(sides, rng)?)?;
+
+            (count, Die::Fair(sides))
+        }
+        Expr::Explode { inner, .. } if inner.contains_explode() => {
+            return Err(EvalError::NestedExplode);
+        }
+        Expr::Explode {
+            inner,
+            threshold,
+            depth,
+        } => {
+            let (count, die) = pool(inner, rng)?;
+            let faces = die.faces()?;
+            let (op, value) = match threshold {
+                Some(Condition { op, value }) => (*op, roll(value, rng)?),
+                None => (BinaryOp::Eq, faces[faces.len() - 1]),
+            };
+
+            let explodes = faces.iter().filter(|&&face| op.holds(face, value)).count();
+
+            if explodes == 0 || explodes == faces.len() {
+                return Ok((count, die));
+            }
+
+            let die = Die::Exploded {
+                die: Box::new(die),
+                op,
+                value,
+                depth: *depth,
+            };
+
+            (count, die)
+        }
+        Expr::Reroll {
+            inner,
+            condition: Condition { op, value },
+            strategy,
+        } => {
+            let (count, die) = pool(inner, rng)?;
+            let value = roll(value, rng)?;
+
+            if die.faces()?.iter().all(|&face| op.holds(face, value)) {
+                return Ok((count, die));
+            }
+
+            let die = Die::Rerolled {
+                die: Box::new(die),
+                op: *op,
+                value,
+                strategy: *strategy,
+            };
+
+            (count, die)
+        }
+        Expr::Group(inner) => pool(inner, rng)?,
+        _ => (1, Die::Whole(expr)),
+    };
+
+    Ok(pool)
+}
+
+impl Die<'_> {
+    fn roll(&self, rng: &mut impl Rng) -> Result<i32, EvalError> {
+        let result = match self {
+            Die::Fair(sides) => rng.roll_die(*sides),
+            Die::Whole(expr) => roll(expr, rng)?,
+            // A die that would still explode after the last reroll is rolled
+            // again from scratch, matching how `distribution` drops those
+            // outcomes.
+            Die::Exploded {
+                die,
+                op,
+                value,
+                depth,
+            } => loop {
+                let mut total = 0;
+
+                for _ in 0..=*depth {
+                    let n = die.roll(rng)?;
+                    total += n;
+
+                    if !op.holds(n, *value) {
+                        return Ok(total);
+                    }
+                }
+            },
+            Die::Rerolled {
+                die,
+                op,
+                value,
+                strategy,
+            } => {
+                let n = die.roll(rng)?;
+
+                if !op.holds(n, *value) {
+                    return Ok(n);
+                }
+
+                match strategy {
+                    RerollStrategy::Once => die.roll(rng)?,
+                    RerollStrategy::Until => loop {
+                        let n = die.roll(rng)?;
+
+                        if !op.holds(n, *value) {
+                            break n;
+                        }
+                    },
+                }
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// Every value the die can roll, lowest first.
+    ///
+    /// Only [`Die::Whole`] needs the full [`distribution`] of its expression
+    /// for this; the rest follow from the faces of the die they modify.
+    fn faces(&self) -> Result<Vec<i32>, EvalError> {
+        let faces = match self {
+            Die::Fair(sides) => (1..=*sides).collect(),
+            Die::Whole(expr) => distribution(expr)?.outcomes().map(|(n, _)| n).collect(),
+            Die::Exploded {
+                die,
+                op,
+                value,
+                depth,
+            } => {
+                let (exploding, stopping): (Vec<i32>, Vec<i32>) = die
+                    .faces()?
+                    .into_iter()
+                    .partition(|&face| op.holds(face, *value));
+                let mut faces = Vec::new();
+                // The totals of the faces that have exploded so far.
+                let mut exploded = vec![0];
+
+                for level in 0..=*depth {
+                    for total in &exploded {
+                        faces.extend(stopping.iter().map(|face| total + face));
+                    }
+
+                    if level < *depth {
+                        exploded = exploded
+                            .iter()
+                            .flat_map(|total| exploding.iter().map(move |face| total + face))
+                            .collect();
+                        exploded.sort_unstable();
+                        exploded.dedup();
+                    }
+                }
+
+                faces.sort_unstable();
+                faces.dedup();
+                faces
+            }
+            Die::Rerolled {
+                die,
+                strategy: RerollStrategy::Once,
+                ..
+            } => die.faces()?,
+            Die::Rerolled {
+                die,
+                op,
+                value,
+                strategy: RerollStrategy::Until,
+            } => die
+                .faces()?
+                .into_iter()
+                .filter(|&face| !op.holds(face, *value))
+                .collect(),
+        };
+
+        Ok(faces)
+    }
+}
+
+/// The sum of the highest `keep` of `dice`, or the lowest if `highest` is
+/// false.
+fn sum_of_kept(mut dice: Vec<i32>, keep: i32, highest: bool) -> i32 {
+    dice.sort_unstable_by(|a, b| b.cmp(a));
+
+    if !highest {
+        dice.reverse();
+    }
+
+    dice.into_iter().take(keep.max(0) as usize).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, tokenize_strict};
+
+    fn parse_str(input: &str) -> Expr {
+        parse(&tokenize_strict(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_seeded_rolls_are_reproducible() {
+        let expr = parse_str("3d6");
+        let mut rng = XorShift::new(42);
+
+        let totals: Vec<i32> = (0..5).map(|_| roll(&expr, &mut rng).unwrap()).collect();
+
+        assert_eq!(vec![7, 11, 12, 11, 14], totals);
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_rolls() {
+        let expr = parse_str("4d6k3 + d20! - 2");
+        let mut first = XorShift::new(7);
+        let mut second = XorShift::new(7);
+
+        for _ in 0..100 {
+            assert_eq!(
+                roll(&expr, &mut first).unwrap(),
+                roll(&expr, &mut second).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_rolls_stay_in_range() {
+        let mut rng = XorShift::new(1);
+
+        for input in [
+            "4d6 drop 1",
+            "4d6kl3",
+            "4d6dh1",
+            "(4d6) keep low 3",
+            "max(3d6, 3)",
+        ] {
+            let expr = parse_str(input);
+
+            for _ in 0..1000 {
+                assert!((3..=18).contains(&roll(&expr, &mut rng).unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_face_comes_up() {
+        let mut rng = XorShift::new(0);
+        let mut seen = [false; 6];
+
+        for _ in 0..100 {
+            seen[rng.roll_die(6) as usize - 1] = true;
+        }
+
+        assert!(seen.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn test_rolls_match_distribution() {
+        let expr = parse_str("4d6k3 + 2d6 explode on > 4");
+        let mut rng = XorShift::new(3);
+
+        let total: i32 = (0..20_000).map(|_| roll(&expr, &mut rng).unwrap()).sum();
+        let mean = f64::from(total) / 20_000.0;
+
+        assert!((mean - distribution(&expr).unwrap().mean()).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_rerolls_match_distribution() {
+        let mut rng = XorShift::new(5);
+
+        for input in [
+            "3d6 reroll once <= 2",
+            "3d6 reroll until < 3",
+            "(d4) rr == 1",
+            "3d6 rr == 1 !",
+            "(3d6 rr <= 2)!",
+            "2d6! rr <= 3",
+            "(d6 + 1) rr == 2",
+            "4d6 rr == 1 keep 3",
+        ] {
+            let expr = parse_str(input);
+            let total: i32 = (0..20_000).map(|_| roll(&expr, &mut rng).unwrap()).sum();
+            let mean = f64::from(total) / 20_000.0;
+
+            assert!(
+                (mean - distribution(&expr).unwrap().mean()).abs() < 0.1,
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_reroll_until_never_keeps_a_triggering_face() {
+        let expr = parse_str("4d6 reroll until <= 2");
+        let mut rng = XorShift::new(9);
+
+        for _ in 0..1000 {
+            assert!(roll(&expr, &mut rng).unwrap() >= 12);
+        }
+    }
+
+    #[test]
+    fn test_counting_successes() {
+        let expr = parse_str("5d10 >= 8");
+        let mut rng = XorShift::new(11);
+
+        let total: i32 = (0..20_000).map(|_| roll(&expr, &mut rng).unwrap()).sum();
+        let mean = f64::from(total) / 20_000.0;
+
+        assert!((mean - 1.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut rng = XorShift::new(13);
+        let results: Vec<_> = (0..100)
+            .map(|_| roll(&parse_str("6 / (d2 - 1)"), &mut rng))
+            .collect();
+
+        assert!(results.contains(&Ok(6)));
+        assert!(results.contains(&Err(EvalError::DivisionByZero)));
+        assert_eq!(
+            Err(EvalError::TooFewSides(0)),
+            roll(&parse_str("0d0"), &mut rng)
+        );
+        assert_eq!(
+            Err(EvalError::Overflow),
+            roll(&parse_str("d6 * 1000000000"), &mut rng)
+        );
+        assert_eq!(
+            Err(EvalError::NoArguments),
+            roll(&Expr::Max(Vec::new()), &mut rng)
+        );
+        assert_eq!(
+            Err(EvalError::NestedExplode),
+            roll(&parse_str("(2d6!) explode on >= 5"), &mut rng)
+        );
+    }
+
+    #[test]
+    fn test_drop_overflow_matches_distribution() {
+        let mut rng = XorShift::new(17);
+
+        for input in [
+            "4d6dl(-2147483647 - 1)",
+            "4d6 drop (-2147483647 - 1)",
+            "(4d6)dh(-2147483647 - 1)",
+        ] {
+            let expr = parse_str(input);
+
+            assert_eq!(Err(EvalError::Overflow), distribution(&expr), "{input}");
+            assert_eq!(Err(EvalError::Overflow), roll(&expr, &mut rng), "{input}");
+        }
+    }
+}
@@ -1,10 +1,30 @@
-use std::{iter::Peekable, str::Chars};
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::Peekable,
+    mem,
+    num::IntErrorKind,
+    str::Chars,
+};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Which spelling of a keyword with an abbreviation was used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeywordForm {
+    /// The abbreviated spelling, like `d` or `k`.
+    Short,
+    /// The spelled-out word, like `die` or `keep`.
+    Long,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenType {
     Int(i32),
-    Die(bool),
-    Keep(bool),
+    Float(f64),
+    Die(KeywordForm),
+    Keep(KeywordForm),
     Drop,
     Explode,
     Emphasis,
@@ -19,171 +39,928 @@ pub enum TokenType {
     Slash,
     Dot,
     Bang,
+    BangEqual,
     Eof,
     Less,
     LessEqual,
     Greater,
     GreaterEqual,
     EqualEqual,
+    /// A lone `=`. Not used by any expression yet; reserved for assignment.
+    Equal,
+    /// A die immediately followed by `%`, as in `d%`, meaning a d100.
+    PercentileDie,
+    /// A `%` that isn't part of `d%`. Reserved for a modulo operator.
+    Percent,
+    /// `dF`, a Fate (or Fudge) die with faces -1, 0, and +1.
+    FateDie,
+    Caret,
+    Reroll(KeywordForm),
+    Comma,
+    Min,
+    Max,
+    LeftBracket,
+    RightBracket,
+    Advantage,
+    Disadvantage,
+    Colon,
+    DotDot,
+    Once,
+    Until,
+    If,
+    Else,
+    Then,
+    Let,
+    In,
+    True,
+    False,
+    Fn,
+    Return,
+    Count,
+    Sum,
+    Sort,
+    KeepHighest,
+    KeepLowest,
+    DropHighest,
+    DropLowest,
+    On,
+    High,
+    Low,
+    Semicolon,
+}
+
+/// Floats are equal only when their bits are, so `Float(0.0)` and
+/// `Float(-0.0)` differ, as they do to [`Ord`] and [`Hash`].
+impl PartialEq for TokenType {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TokenType {}
+
+impl TokenType {
+    /// The position of this variant in the declaration of `TokenType`.
+    fn variant_index(&self) -> usize {
+        match self {
+            TokenType::Int(_) => 0,
+            TokenType::Float(_) => 1,
+            TokenType::Die(_) => 2,
+            TokenType::Keep(_) => 3,
+            TokenType::Drop => 4,
+            TokenType::Explode => 5,
+            TokenType::Emphasis => 6,
+            TokenType::Unrecognized(_) => 7,
+            TokenType::LeftParen => 8,
+            TokenType::RightParen => 9,
+            TokenType::LeftBrace => 10,
+            TokenType::RightBrace => 11,
+            TokenType::Plus => 12,
+            TokenType::Minus => 13,
+            TokenType::Star => 14,
+            TokenType::Slash => 15,
+            TokenType::Dot => 16,
+            TokenType::Bang => 17,
+            TokenType::BangEqual => 18,
+            TokenType::Eof => 19,
+            TokenType::Less => 20,
+            TokenType::LessEqual => 21,
+            TokenType::Greater => 22,
+            TokenType::GreaterEqual => 23,
+            TokenType::EqualEqual => 24,
+            TokenType::Equal => 25,
+            TokenType::PercentileDie => 26,
+            TokenType::Percent => 27,
+            TokenType::FateDie => 28,
+            TokenType::Caret => 29,
+            TokenType::Reroll(_) => 30,
+            TokenType::Comma => 31,
+            TokenType::Min => 32,
+            TokenType::Max => 33,
+            TokenType::LeftBracket => 34,
+            TokenType::RightBracket => 35,
+            TokenType::Advantage => 36,
+            TokenType::Disadvantage => 37,
+            TokenType::Colon => 38,
+            TokenType::DotDot => 39,
+            TokenType::Once => 40,
+            TokenType::Until => 41,
+            TokenType::If => 42,
+            TokenType::Else => 43,
+            TokenType::Then => 44,
+            TokenType::Let => 45,
+            TokenType::In => 46,
+            TokenType::True => 47,
+            TokenType::False => 48,
+            TokenType::Fn => 49,
+            TokenType::Return => 50,
+            TokenType::Count => 51,
+            TokenType::Sum => 52,
+            TokenType::Sort => 53,
+            TokenType::KeepHighest => 54,
+            TokenType::KeepLowest => 55,
+            TokenType::DropHighest => 56,
+            TokenType::DropLowest => 57,
+            TokenType::On => 58,
+            TokenType::High => 59,
+            TokenType::Low => 60,
+            TokenType::Semicolon => 61,
+        }
+    }
+}
+
+impl PartialOrd for TokenType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders token types by variant, in declaration order, and then by payload.
+/// Floats are ordered with [`f64::total_cmp`], so `-0.0` comes before `0.0`.
+impl Ord for TokenType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (TokenType::Int(a), TokenType::Int(b)) => a.cmp(b),
+            (TokenType::Float(a), TokenType::Float(b)) => a.total_cmp(b),
+            (TokenType::Die(a), TokenType::Die(b)) => a.cmp(b),
+            (TokenType::Keep(a), TokenType::Keep(b)) => a.cmp(b),
+            (TokenType::Reroll(a), TokenType::Reroll(b)) => a.cmp(b),
+            (TokenType::Unrecognized(a), TokenType::Unrecognized(b)) => a.cmp(b),
+            _ => self.variant_index().cmp(&other.variant_index()),
+        }
+    }
+}
+
+impl Hash for TokenType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match self {
+            TokenType::Int(n) => n.hash(state),
+            TokenType::Float(x) => x.to_bits().hash(state),
+            TokenType::Die(form) | TokenType::Keep(form) | TokenType::Reroll(form) => {
+                form.hash(state)
+            }
+            TokenType::Unrecognized(literal) => literal.hash(state),
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenType::Int(n) => write!(f, "{n}"),
+            TokenType::Float(x) => write!(f, "{x}"),
+            TokenType::Die(KeywordForm::Long) => write!(f, "die"),
+            TokenType::Die(KeywordForm::Short) => write!(f, "d"),
+            TokenType::Keep(KeywordForm::Long) => write!(f, "keep"),
+            TokenType::Keep(KeywordForm::Short) => write!(f, "k"),
+            TokenType::Drop => write!(f, "drop"),
+            TokenType::Explode => write!(f, "explode"),
+            TokenType::Emphasis => write!(f, "emphasis"),
+            TokenType::Unrecognized(literal) => write!(f, "{literal}"),
+            TokenType::LeftParen => write!(f, "("),
+            TokenType::RightParen => write!(f, ")"),
+            TokenType::LeftBrace => write!(f, "{{"),
+            TokenType::RightBrace => write!(f, "}}"),
+            TokenType::Plus => write!(f, "+"),
+            TokenType::Minus => write!(f, "-"),
+            TokenType::Star => write!(f, "*"),
+            TokenType::Slash => write!(f, "/"),
+            TokenType::Dot => write!(f, "."),
+            TokenType::Bang => write!(f, "!"),
+            TokenType::BangEqual => write!(f, "!="),
+            TokenType::Eof => write!(f, "<EOF>"),
+            TokenType::Less => write!(f, "<"),
+            TokenType::LessEqual => write!(f, "<="),
+            TokenType::Greater => write!(f, ">"),
+            TokenType::GreaterEqual => write!(f, ">="),
+            TokenType::EqualEqual => write!(f, "=="),
+            TokenType::Equal => write!(f, "="),
+            TokenType::PercentileDie => write!(f, "d%"),
+            TokenType::Percent => write!(f, "%"),
+            TokenType::FateDie => write!(f, "dF"),
+            TokenType::Caret => write!(f, "^"),
+            TokenType::Reroll(KeywordForm::Long) => write!(f, "reroll"),
+            TokenType::Reroll(KeywordForm::Short) => write!(f, "rr"),
+            TokenType::Comma => write!(f, ","),
+            TokenType::Min => write!(f, "min"),
+            TokenType::Max => write!(f, "max"),
+            TokenType::LeftBracket => write!(f, "["),
+            TokenType::RightBracket => write!(f, "]"),
+            TokenType::Advantage => write!(f, "advantage"),
+            TokenType::Disadvantage => write!(f, "disadvantage"),
+            TokenType::Colon => write!(f, ":"),
+            TokenType::DotDot => write!(f, ".."),
+            TokenType::Once => write!(f, "once"),
+            TokenType::Until => write!(f, "until"),
+            TokenType::If => write!(f, "if"),
+            TokenType::Else => write!(f, "else"),
+            TokenType::Then => write!(f, "then"),
+            TokenType::Let => write!(f, "let"),
+            TokenType::In => write!(f, "in"),
+            TokenType::True => write!(f, "true"),
+            TokenType::False => write!(f, "false"),
+            TokenType::Fn => write!(f, "fn"),
+            TokenType::Return => write!(f, "return"),
+            TokenType::Count => write!(f, "count"),
+            TokenType::Sum => write!(f, "sum"),
+            TokenType::Sort => write!(f, "sort"),
+            TokenType::KeepHighest => write!(f, "kh"),
+            TokenType::KeepLowest => write!(f, "kl"),
+            TokenType::DropHighest => write!(f, "dh"),
+            TokenType::DropLowest => write!(f, "dl"),
+            TokenType::On => write!(f, "on"),
+            TokenType::High => write!(f, "high"),
+            TokenType::Low => write!(f, "low"),
+            TokenType::Semicolon => write!(f, ";"),
+        }
+    }
+}
+
+/// A half-open range of byte offsets into the source string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The slice of `source` covered by this span.
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start..self.end]
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub token_type: TokenType,
     pub line: usize,
     pub column: usize,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, line: usize, column: usize) -> Self {
+    pub fn new(token_type: TokenType, line: usize, column: usize, span: Span) -> Self {
         Self {
             token_type,
             line,
             column,
+            span,
+        }
+    }
+
+    /// The column just past the last character of this token.
+    ///
+    /// Tokens never contain line breaks or non-ASCII characters, so this is
+    /// the starting column plus the length of the token's span.
+    pub fn end_column(&self) -> usize {
+        self.column + self.span.end - self.span.start
+    }
+
+    /// Whether this is an arithmetic, logical, or comparison operator.
+    pub fn is_operator(&self) -> bool {
+        matches!(
+            self.token_type,
+            TokenType::Plus
+                | TokenType::Minus
+                | TokenType::Star
+                | TokenType::Slash
+                | TokenType::Bang
+                | TokenType::BangEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::EqualEqual
+                | TokenType::Caret
+        )
+    }
+
+    /// Whether this separates items in a list, like the arguments to a call,
+    /// or one statement from the next.
+    pub fn is_separator(&self) -> bool {
+        matches!(
+            self.token_type,
+            TokenType::Comma | TokenType::Colon | TokenType::Semicolon
+        )
+    }
+
+    /// Whether this is a literal value: a number, `true`, or `false`.
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self.token_type,
+            TokenType::Int(_) | TokenType::Float(_) | TokenType::True | TokenType::False
+        )
+    }
+
+    /// Whether this is one of the language's built-in words, like `d` or `keep`.
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self.token_type,
+            TokenType::Die(_)
+                | TokenType::Keep(_)
+                | TokenType::Drop
+                | TokenType::Explode
+                | TokenType::Emphasis
+                | TokenType::PercentileDie
+                | TokenType::FateDie
+                | TokenType::Reroll(_)
+                | TokenType::Min
+                | TokenType::Max
+                | TokenType::Advantage
+                | TokenType::Disadvantage
+                | TokenType::Once
+                | TokenType::Until
+                | TokenType::If
+                | TokenType::Else
+                | TokenType::Then
+                | TokenType::Let
+                | TokenType::In
+                | TokenType::Fn
+                | TokenType::Return
+                | TokenType::Count
+                | TokenType::Sum
+                | TokenType::Sort
+                | TokenType::KeepHighest
+                | TokenType::KeepLowest
+                | TokenType::DropHighest
+                | TokenType::DropLowest
+                | TokenType::On
+                | TokenType::High
+                | TokenType::Low
+        )
+    }
+
+    /// The value of an `Int` token, or `None` for any other token.
+    pub fn as_int(&self) -> Option<i32> {
+        match self.token_type {
+            TokenType::Int(n) => Some(n),
+            _ => None,
         }
     }
 }
 
+impl PartialOrd for Token {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders tokens by source position. Tokens at the same position, which can
+/// only come from different sources, fall back to comparing type and span.
+impl Ord for Token {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.line, self.column)
+            .cmp(&(other.line, other.column))
+            .then_with(|| self.token_type.cmp(&other.token_type))
+            .then_with(|| self.span.cmp(&other.span))
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}:{}] {}", self.line, self.column, self.token_type)
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenError {
-    UnsupportedToken(String),
-    InvalidNumberToken(String),
+    UnsupportedToken {
+        literal: String,
+        line: usize,
+        column: usize,
+    },
+    InvalidNumberToken {
+        literal: String,
+        line: usize,
+        column: usize,
+    },
+    NumberOutOfRange {
+        literal: String,
+        line: usize,
+        column: usize,
+    },
+    /// A `/*` with no matching `*/`, positioned at the opening `/*`.
+    UnterminatedBlockComment { line: usize, column: usize },
+}
+
+impl TokenError {
+    pub fn line(&self) -> usize {
+        match self {
+            TokenError::UnsupportedToken { line, .. }
+            | TokenError::InvalidNumberToken { line, .. }
+            | TokenError::NumberOutOfRange { line, .. }
+            | TokenError::UnterminatedBlockComment { line, .. } => *line,
+        }
+    }
+
+    pub fn column(&self) -> usize {
+        match self {
+            TokenError::UnsupportedToken { column, .. }
+            | TokenError::InvalidNumberToken { column, .. }
+            | TokenError::NumberOutOfRange { column, .. }
+            | TokenError::UnterminatedBlockComment { column, .. } => *column,
+        }
+    }
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::UnsupportedToken { literal, .. } => {
+                write!(f, "unsupported character '{literal}'")
+            }
+            TokenError::InvalidNumberToken { literal, .. } => {
+                write!(f, "invalid number literal '{literal}'")
+            }
+            TokenError::NumberOutOfRange { literal, .. } => {
+                write!(f, "number literal '{literal}' is out of range")
+            }
+            TokenError::UnterminatedBlockComment { .. } => write!(f, "unterminated block comment"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Tokenizer over a source string that keeps track of its own position.
+///
+/// Once the end of the input is reached, every further call to
+/// [`Lexer::next_token`] returns another `Eof` token at the same position.
+pub struct Lexer<'a> {
+    input: Peekable<Chars<'a>>,
+    current_line: usize,
+    current_column: usize,
+    current_offset: usize,
+    reached_eof: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input: input.chars().peekable(),
+            current_line: 1,
+            current_column: 1,
+            current_offset: 0,
+            reached_eof: false,
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<Token, TokenError> {
+        if self.reached_eof {
+            return Ok(Token::new(
+                TokenType::Eof,
+                self.current_line,
+                self.current_column,
+                Span::new(self.current_offset, self.current_offset),
+            ));
+        }
+
+        let token = read_token(
+            &mut self.input,
+            &mut self.current_line,
+            &mut self.current_column,
+            &mut self.current_offset,
+        )?;
+
+        if token.token_type == TokenType::Eof {
+            self.reached_eof = true;
+        }
+
+        Ok(token)
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, TokenError>;
+
+    /// Yields every token up to and including `Eof`, then `None`.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reached_eof {
+            return None;
+        }
+
+        Some(self.next_token())
+    }
 }
 
+/// Reads the next token from `input`, skipping whitespace and comments.
+///
+/// The position arguments always describe the character about to be read:
+/// `current_line` and `current_column` are 1-based, and `current_offset` is its
+/// byte offset into the source. Every character consumed moves them past it,
+/// including one that causes an error, so lexing can resume after a failure.
+/// Columns count Unicode scalar values, not bytes.
 pub fn read_token(
     input: &mut Peekable<Chars>,
     current_line: &mut usize,
     current_column: &mut usize,
+    current_offset: &mut usize,
 ) -> Result<Token, TokenError> {
-    while let Some(next_char) = input.next() {
-        let token = match next_char {
-            ' ' => None,
+    loop {
+        let starting_column = *current_column;
+        let starting_offset = *current_offset;
+
+        let Some(next_char) = advance(input, current_column, current_offset) else {
+            break;
+        };
+
+        let token_type = match next_char {
+            // Tabs advance a single column, and a `\r` is skipped so that `\r\n`
+            // only counts as one line break.
+            ' ' | '\t' | '\r' => None,
             '\n' => {
                 *current_line += 1;
-                *current_column = 0;
+                *current_column = 1;
                 None
             }
-            '(' => Some(Token::new(
-                TokenType::LeftParen,
-                *current_line,
-                *current_column,
-            )),
-            ')' => Some(Token::new(
-                TokenType::RightParen,
-                *current_line,
-                *current_column,
-            )),
-            '{' => Some(Token::new(
-                TokenType::LeftBrace,
-                *current_line,
-                *current_column,
-            )),
-            '}' => Some(Token::new(
-                TokenType::RightBrace,
-                *current_line,
-                *current_column,
-            )),
-            '+' => Some(Token::new(TokenType::Plus, *current_line, *current_column)),
-            '-' => Some(Token::new(TokenType::Minus, *current_line, *current_column)),
-            '*' => Some(Token::new(TokenType::Star, *current_line, *current_column)),
-            '/' => Some(Token::new(TokenType::Slash, *current_line, *current_column)),
-            '.' => Some(Token::new(TokenType::Dot, *current_line, *current_column)),
-            '!' => Some(Token::new(TokenType::Bang, *current_line, *current_column)),
-            '<' => {
-                let token_type = match input.peek() {
-                    Some('=') => TokenType::LessEqual,
-                    _ => TokenType::Less,
-                };
-
-                Some(Token::new(token_type, *current_line, *current_column))
+            '#' => {
+                skip_line_comment(input, current_column, current_offset);
+                None
             }
-            first_digit if first_digit.is_ascii_digit() => {
-                let starting_column = *current_column;
+            '(' => Some(TokenType::LeftParen),
+            ')' => Some(TokenType::RightParen),
+            '{' => Some(TokenType::LeftBrace),
+            '}' => Some(TokenType::RightBrace),
+            '[' => Some(TokenType::LeftBracket),
+            ']' => Some(TokenType::RightBracket),
+            ';' => Some(TokenType::Semicolon),
+            '+' => Some(TokenType::Plus),
+            '-' => Some(TokenType::Minus),
+            '*' => Some(TokenType::Star),
+            '/' => match input.peek() {
+                Some('/') => {
+                    skip_line_comment(input, current_column, current_offset);
+                    None
+                }
+                Some('*') => {
+                    let starting_line = *current_line;
+
+                    advance(input, current_column, current_offset);
+
+                    if !skip_block_comment(input, current_line, current_column, current_offset) {
+                        return Err(TokenError::UnterminatedBlockComment {
+                            line: starting_line,
+                            column: starting_column,
+                        });
+                    }
+
+                    None
+                }
+                _ => Some(TokenType::Slash),
+            },
+            '.' => match input.peek() {
+                Some('.') => {
+                    advance(input, current_column, current_offset);
+                    Some(TokenType::DotDot)
+                }
+                _ => Some(TokenType::Dot),
+            },
+            '%' => Some(TokenType::Percent),
+            '^' => Some(TokenType::Caret),
+            ',' => Some(TokenType::Comma),
+            ':' => Some(TokenType::Colon),
+            '!' => match input.peek() {
+                Some('=') => {
+                    advance(input, current_column, current_offset);
+                    Some(TokenType::BangEqual)
+                }
+                _ => Some(TokenType::Bang),
+            },
+            '<' => match input.peek() {
+                Some('=') => {
+                    advance(input, current_column, current_offset);
+                    Some(TokenType::LessEqual)
+                }
+                _ => Some(TokenType::Less),
+            },
+            '>' => match input.peek() {
+                Some('=') => {
+                    advance(input, current_column, current_offset);
+                    Some(TokenType::GreaterEqual)
+                }
+                _ => Some(TokenType::Greater),
+            },
+            '=' => match input.peek() {
+                Some('=') => {
+                    advance(input, current_column, current_offset);
+                    Some(TokenType::EqualEqual)
+                }
+                _ => Some(TokenType::Equal),
+            },
+            '0' if matches!(input.peek(), Some('x' | 'X')) => {
+                let mut n = vec!['0', advance(input, current_column, current_offset).unwrap()];
 
+                while input.peek().is_some_and(char::is_ascii_hexdigit) {
+                    n.push(advance(input, current_column, current_offset).unwrap());
+                }
+
+                let n = n.into_iter().collect::<String>();
+
+                match i32::from_str_radix(&n[2..], 16) {
+                    Ok(n) => Some(TokenType::Int(n)),
+                    Err(error) => {
+                        return Err(number_error(
+                            error.kind(),
+                            n,
+                            *current_line,
+                            starting_column,
+                        ))
+                    }
+                }
+            }
+            // A digit separator can't start a number, but report `_5` as a bad
+            // number rather than an unsupported character.
+            '_' if input.peek().is_some_and(char::is_ascii_digit) => {
+                let mut n = vec!['_'];
+
+                while input.peek().is_some_and(is_digit_or_separator) {
+                    n.push(advance(input, current_column, current_offset).unwrap());
+                }
+
+                return Err(TokenError::InvalidNumberToken {
+                    literal: n.into_iter().collect(),
+                    line: *current_line,
+                    column: starting_column,
+                });
+            }
+            first_digit if first_digit.is_ascii_digit() => {
                 let mut n = vec![first_digit];
 
-                while input.peek().is_some() && input.peek().unwrap().is_ascii_digit() {
-                    n.push(input.next().unwrap());
-                    *current_column += 1;
+                while input.peek().is_some_and(is_digit_or_separator) {
+                    n.push(advance(input, current_column, current_offset).unwrap());
+                }
+
+                if n.last() == Some(&'_') || n.windows(2).any(|pair| pair == ['_', '_']) {
+                    return Err(TokenError::InvalidNumberToken {
+                        literal: n.into_iter().collect(),
+                        line: *current_line,
+                        column: starting_column,
+                    });
+                }
+
+                let is_float = input.peek() == Some(&'.') && is_followed_by_digit(input);
+
+                if is_float {
+                    n.push(advance(input, current_column, current_offset).unwrap());
+
+                    while input.peek().is_some() && input.peek().unwrap().is_ascii_digit() {
+                        n.push(advance(input, current_column, current_offset).unwrap());
+                    }
                 }
 
                 let n = n.into_iter().collect::<String>();
+                let digits = n.replace('_', "");
 
-                match n.parse() {
-                    Ok(n) => Some(Token::new(
-                        TokenType::Int(n),
-                        *current_line,
-                        starting_column,
-                    )),
-                    Err(_) => return Err(TokenError::InvalidNumberToken(n)),
+                if is_float {
+                    match digits.parse() {
+                        Ok(x) => Some(TokenType::Float(x)),
+                        Err(_) => {
+                            return Err(TokenError::InvalidNumberToken {
+                                literal: n,
+                                line: *current_line,
+                                column: starting_column,
+                            })
+                        }
+                    }
+                } else {
+                    match digits.parse() {
+                        Ok(n) => Some(TokenType::Int(n)),
+                        Err(error) => {
+                            return Err(number_error(
+                                error.kind(),
+                                n,
+                                *current_line,
+                                starting_column,
+                            ))
+                        }
+                    }
                 }
             }
-            first_char if first_char.is_ascii_alphabetic() => {
-                let starting_column = *current_column;
+            first_char if is_keyword_character(&first_char) => {
+                let mut literal = String::from(first_char);
 
-                let mut chars = vec![first_char];
+                while let Some(c) = input.peek().copied().filter(is_keyword_character) {
+                    // A dice modifier followed by a digit ends there, so `d6` and
+                    // `kh3` still split, while `roll1` and `max1` stay single
+                    // identifiers.
+                    if c.is_ascii_digit()
+                        && keyword(&literal).as_ref().is_some_and(is_dice_modifier)
+                    {
+                        break;
+                    }
 
-                while input.peek().is_some() && is_keyword_character(input.peek().unwrap()) {
-                    chars.push(input.next().unwrap());
-                    *current_column += 1;
+                    literal.push(c);
+                    advance(input, current_column, current_offset);
                 }
 
-                let literal = chars.into_iter().collect::<String>();
-
-                match literal.as_str() {
-                    "d" => Some(Token::new(
-                        TokenType::Die(false),
-                        *current_line,
-                        starting_column,
-                    )),
-                    "die" => Some(Token::new(
-                        TokenType::Die(true),
-                        *current_line,
-                        starting_column,
-                    )),
-                    "k" => Some(Token::new(
-                        TokenType::Keep(false),
-                        *current_line,
-                        starting_column,
-                    )),
-                    "keep" => Some(Token::new(
-                        TokenType::Keep(true),
-                        *current_line,
-                        starting_column,
-                    )),
-                    "drop" => Some(Token::new(TokenType::Drop, *current_line, starting_column)),
-                    "explode" => Some(Token::new(
-                        TokenType::Explode,
-                        *current_line,
-                        starting_column,
-                    )),
-                    "emphasis" => Some(Token::new(
-                        TokenType::Emphasis,
-                        *current_line,
-                        starting_column,
-                    )),
-                    _ => Some(Token::new(
-                        TokenType::Unrecognized(literal),
-                        *current_line,
-                        starting_column,
-                    )),
+                match keyword(&literal) {
+                    Some(TokenType::Die(_)) if input.peek() == Some(&'%') => {
+                        advance(input, current_column, current_offset);
+                        Some(TokenType::PercentileDie)
+                    }
+                    Some(token_type) => Some(token_type),
+                    None => Some(TokenType::Unrecognized(literal)),
                 }
             }
-            c => return Err(TokenError::UnsupportedToken(String::from(c))),
+            c => {
+                return Err(TokenError::UnsupportedToken {
+                    literal: String::from(c),
+                    line: *current_line,
+                    column: starting_column,
+                })
+            }
         };
 
-        *current_column += 1;
+        if let Some(token_type) = token_type {
+            return Ok(Token::new(
+                token_type,
+                *current_line,
+                starting_column,
+                Span::new(starting_offset, *current_offset),
+            ));
+        }
+    }
+
+    Ok(Token::new(
+        TokenType::Eof,
+        *current_line,
+        *current_column,
+        Span::new(*current_offset, *current_offset),
+    ))
+}
+
+/// Looks up the keyword spelled by `literal`, if any.
+///
+/// Keywords are case-insensitive, so `2D6` and `KEEP` work the same as their
+/// lowercase forms. This means `D` can never be an identifier.
+fn keyword(literal: &str) -> Option<TokenType> {
+    match literal.to_ascii_lowercase().as_str() {
+        "d" => Some(TokenType::Die(KeywordForm::Short)),
+        "die" => Some(TokenType::Die(KeywordForm::Long)),
+        "df" => Some(TokenType::FateDie),
+        "k" => Some(TokenType::Keep(KeywordForm::Short)),
+        "keep" => Some(TokenType::Keep(KeywordForm::Long)),
+        "drop" => Some(TokenType::Drop),
+        "explode" => Some(TokenType::Explode),
+        "emphasis" => Some(TokenType::Emphasis),
+        "rr" => Some(TokenType::Reroll(KeywordForm::Short)),
+        "reroll" => Some(TokenType::Reroll(KeywordForm::Long)),
+        "min" => Some(TokenType::Min),
+        "max" => Some(TokenType::Max),
+        "advantage" => Some(TokenType::Advantage),
+        "disadvantage" => Some(TokenType::Disadvantage),
+        "once" => Some(TokenType::Once),
+        "until" => Some(TokenType::Until),
+        "if" => Some(TokenType::If),
+        "else" => Some(TokenType::Else),
+        "then" => Some(TokenType::Then),
+        "let" => Some(TokenType::Let),
+        "in" => Some(TokenType::In),
+        "true" => Some(TokenType::True),
+        "false" => Some(TokenType::False),
+        "fn" => Some(TokenType::Fn),
+        "return" => Some(TokenType::Return),
+        "count" => Some(TokenType::Count),
+        "sum" => Some(TokenType::Sum),
+        "sort" => Some(TokenType::Sort),
+        "kh" => Some(TokenType::KeepHighest),
+        "kl" => Some(TokenType::KeepLowest),
+        "dh" => Some(TokenType::DropHighest),
+        "dl" => Some(TokenType::DropLowest),
+        "on" => Some(TokenType::On),
+        "high" => Some(TokenType::High),
+        "low" => Some(TokenType::Low),
+        _ => None,
+    }
+}
+
+/// Whether `token_type` is a short dice keyword that's written right before a
+/// number, like the `d` in `d6` or the `kh` in `kh3`.
+fn is_dice_modifier(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Die(KeywordForm::Short)
+            | TokenType::Keep(KeywordForm::Short)
+            | TokenType::Reroll(KeywordForm::Short)
+            | TokenType::KeepHighest
+            | TokenType::KeepLowest
+            | TokenType::DropHighest
+            | TokenType::DropLowest
+    )
+}
+
+/// Converts a failure to parse an integer literal into the matching error.
+fn number_error(kind: &IntErrorKind, literal: String, line: usize, column: usize) -> TokenError {
+    match kind {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => TokenError::NumberOutOfRange {
+            literal,
+            line,
+            column,
+        },
+        _ => TokenError::InvalidNumberToken {
+            literal,
+            line,
+            column,
+        },
+    }
+}
+
+/// Consumes the next character, moving the column and byte offset past it.
+fn advance(
+    input: &mut Peekable<Chars>,
+    current_column: &mut usize,
+    current_offset: &mut usize,
+) -> Option<char> {
+    let c = input.next()?;
+
+    *current_column += 1;
+    *current_offset += c.len_utf8();
+
+    Some(c)
+}
+
+/// Consumes the rest of the current line, leaving the `\n` (if any) unread.
+fn skip_line_comment(
+    input: &mut Peekable<Chars>,
+    current_column: &mut usize,
+    current_offset: &mut usize,
+) {
+    while input.peek().is_some_and(|c| *c != '\n') {
+        advance(input, current_column, current_offset);
+    }
+}
+
+/// Consumes the body of a block comment whose opening `/*` has already been
+/// read, up to and including the matching `*/`. Block comments nest.
+///
+/// Returns `false` if the input ran out before the comment was closed.
+fn skip_block_comment(
+    input: &mut Peekable<Chars>,
+    current_line: &mut usize,
+    current_column: &mut usize,
+    current_offset: &mut usize,
+) -> bool {
+    let mut depth = 1;
 
-        if let Some(token) = token {
-            return Ok(token);
+    while depth > 0 {
+        match advance(input, current_column, current_offset) {
+            Some('\n') => {
+                *current_line += 1;
+                *current_column = 1;
+            }
+            Some('*') if input.peek() == Some(&'/') => {
+                advance(input, current_column, current_offset);
+                depth -= 1;
+            }
+            Some('/') if input.peek() == Some(&'*') => {
+                advance(input, current_column, current_offset);
+                depth += 1;
+            }
+            Some(_) => {}
+            None => return false,
         }
     }
 
-    Ok(Token::new(TokenType::Eof, *current_line, *current_column))
+    true
+}
+
+/// Whether the character after the next one is an ASCII digit.
+fn is_followed_by_digit(input: &Peekable<Chars>) -> bool {
+    let mut lookahead = input.clone();
+    lookahead.next();
+    lookahead.peek().is_some_and(char::is_ascii_digit)
+}
+
+fn is_digit_or_separator(c: &char) -> bool {
+    c.is_ascii_digit() || *c == '_'
 }
 
 fn is_keyword_character(c: &char) -> bool {
-    c.is_ascii_alphabetic() || *c == '_'
+    c.is_ascii_alphanumeric() || *c == '_'
+}
+
+/// Lexes all of `input`, carrying on past any errors.
+///
+/// The last result is always `Ok` with an `Eof` token.
+pub fn tokenize(input: &str) -> Vec<Result<Token, TokenError>> {
+    Lexer::new(input).collect()
+}
+
+/// Lexes all of `input`, stopping at the first error.
+///
+/// On success the returned tokens always end with `Eof`.
+pub fn tokenize_strict(input: &str) -> Result<Vec<Token>, TokenError> {
+    Lexer::new(input).collect()
 }
 
 #[cfg(test)]
@@ -192,33 +969,27 @@ mod tests {
 
     #[test]
     fn test_single_character_tokens() {
-        let input = "( ) { }\n+ - * /\n. ! -420";
-        let mut input = input.chars().peekable();
+        let input = "( ) { }\n+ - * /\n. ! -420\n= ==";
 
         let expected_tokens: Vec<Token> = vec![
-            Token::new(TokenType::LeftParen, 1, 1),
-            Token::new(TokenType::RightParen, 1, 3),
-            Token::new(TokenType::LeftBrace, 1, 5),
-            Token::new(TokenType::RightBrace, 1, 7),
-            Token::new(TokenType::Plus, 2, 1),
-            Token::new(TokenType::Minus, 2, 3),
-            Token::new(TokenType::Star, 2, 5),
-            Token::new(TokenType::Slash, 2, 7),
-            Token::new(TokenType::Dot, 3, 1),
-            Token::new(TokenType::Bang, 3, 3),
-            Token::new(TokenType::Minus, 3, 5),
-            Token::new(TokenType::Int(420), 3, 6),
-        ];
-
-        let mut actual_tokens: Vec<Token> = vec![];
-
-        let mut current_line = 1;
-        let mut current_column = 1;
+            Token::new(TokenType::LeftParen, 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::RightParen, 1, 3, Span::new(2, 3)),
+            Token::new(TokenType::LeftBrace, 1, 5, Span::new(4, 5)),
+            Token::new(TokenType::RightBrace, 1, 7, Span::new(6, 7)),
+            Token::new(TokenType::Plus, 2, 1, Span::new(8, 9)),
+            Token::new(TokenType::Minus, 2, 3, Span::new(10, 11)),
+            Token::new(TokenType::Star, 2, 5, Span::new(12, 13)),
+            Token::new(TokenType::Slash, 2, 7, Span::new(14, 15)),
+            Token::new(TokenType::Dot, 3, 1, Span::new(16, 17)),
+            Token::new(TokenType::Bang, 3, 3, Span::new(18, 19)),
+            Token::new(TokenType::Minus, 3, 5, Span::new(20, 21)),
+            Token::new(TokenType::Int(420), 3, 6, Span::new(21, 24)),
+            Token::new(TokenType::Equal, 4, 1, Span::new(25, 26)),
+            Token::new(TokenType::EqualEqual, 4, 3, Span::new(27, 29)),
+            Token::new(TokenType::Eof, 4, 5, Span::new(29, 29)),
+        ];
 
-        while input.peek().is_some() {
-            let token = read_token(&mut input, &mut current_line, &mut current_column).unwrap();
-            actual_tokens.push(token);
-        }
+        let actual_tokens = tokenize_strict(input).unwrap();
 
         assert_eq!(expected_tokens, actual_tokens);
     }
@@ -226,25 +997,17 @@ mod tests {
     #[test]
     fn test_integers() {
         let input = "1 10 1234 -420";
-        let mut input = input.chars().peekable();
 
         let expected_tokens: Vec<Token> = vec![
-            Token::new(TokenType::Int(1), 1, 1),
-            Token::new(TokenType::Int(10), 1, 3),
-            Token::new(TokenType::Int(1234), 1, 6),
-            Token::new(TokenType::Minus, 1, 11),
-            Token::new(TokenType::Int(420), 1, 12),
+            Token::new(TokenType::Int(1), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Int(10), 1, 3, Span::new(2, 4)),
+            Token::new(TokenType::Int(1234), 1, 6, Span::new(5, 9)),
+            Token::new(TokenType::Minus, 1, 11, Span::new(10, 11)),
+            Token::new(TokenType::Int(420), 1, 12, Span::new(11, 14)),
+            Token::new(TokenType::Eof, 1, 15, Span::new(14, 14)),
         ];
 
-        let mut actual_tokens: Vec<Token> = vec![];
-
-        let mut current_line = 1;
-        let mut current_column = 1;
-
-        while input.peek().is_some() {
-            let token = read_token(&mut input, &mut current_line, &mut current_column).unwrap();
-            actual_tokens.push(token);
-        }
+        let actual_tokens = tokenize_strict(input).unwrap();
 
         assert_eq!(expected_tokens, actual_tokens);
     }
@@ -252,32 +1015,1306 @@ mod tests {
     #[test]
     fn test_literals() {
         let input = "die d 2d4k6 keep drop explode emphasis";
-        let mut input = input.chars().peekable();
 
         let expected_tokens: Vec<Token> = vec![
-            Token::new(TokenType::Die(true), 1, 1),
-            Token::new(TokenType::Die(false), 1, 5),
-            Token::new(TokenType::Int(2), 1, 7),
-            Token::new(TokenType::Die(false), 1, 8),
-            Token::new(TokenType::Int(4), 1, 9),
-            Token::new(TokenType::Keep(false), 1, 10),
-            Token::new(TokenType::Int(6), 1, 11),
-            Token::new(TokenType::Keep(true), 1, 13),
-            Token::new(TokenType::Drop, 1, 18),
-            Token::new(TokenType::Explode, 1, 23),
-            Token::new(TokenType::Emphasis, 1, 31),
+            Token::new(TokenType::Die(KeywordForm::Long), 1, 1, Span::new(0, 3)),
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 5, Span::new(4, 5)),
+            Token::new(TokenType::Int(2), 1, 7, Span::new(6, 7)),
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 8, Span::new(7, 8)),
+            Token::new(TokenType::Int(4), 1, 9, Span::new(8, 9)),
+            Token::new(TokenType::Keep(KeywordForm::Short), 1, 10, Span::new(9, 10)),
+            Token::new(TokenType::Int(6), 1, 11, Span::new(10, 11)),
+            Token::new(TokenType::Keep(KeywordForm::Long), 1, 13, Span::new(12, 16)),
+            Token::new(TokenType::Drop, 1, 18, Span::new(17, 21)),
+            Token::new(TokenType::Explode, 1, 23, Span::new(22, 29)),
+            Token::new(TokenType::Emphasis, 1, 31, Span::new(30, 38)),
+            Token::new(TokenType::Eof, 1, 39, Span::new(38, 38)),
         ];
 
-        let mut actual_tokens: Vec<Token> = vec![];
+        let actual_tokens = tokenize_strict(input).unwrap();
 
-        let mut current_line = 1;
-        let mut current_column = 1;
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_comparison_tokens() {
+        let input = "< <= > >=\n1<=2 3>=4 5>6\n>>";
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Less, 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::LessEqual, 1, 3, Span::new(2, 4)),
+            Token::new(TokenType::Greater, 1, 6, Span::new(5, 6)),
+            Token::new(TokenType::GreaterEqual, 1, 8, Span::new(7, 9)),
+            Token::new(TokenType::Int(1), 2, 1, Span::new(10, 11)),
+            Token::new(TokenType::LessEqual, 2, 2, Span::new(11, 13)),
+            Token::new(TokenType::Int(2), 2, 4, Span::new(13, 14)),
+            Token::new(TokenType::Int(3), 2, 6, Span::new(15, 16)),
+            Token::new(TokenType::GreaterEqual, 2, 7, Span::new(16, 18)),
+            Token::new(TokenType::Int(4), 2, 9, Span::new(18, 19)),
+            Token::new(TokenType::Int(5), 2, 11, Span::new(20, 21)),
+            Token::new(TokenType::Greater, 2, 12, Span::new(21, 22)),
+            Token::new(TokenType::Int(6), 2, 13, Span::new(22, 23)),
+            Token::new(TokenType::Greater, 3, 1, Span::new(24, 25)),
+            Token::new(TokenType::Greater, 3, 2, Span::new(25, 26)),
+            Token::new(TokenType::Eof, 3, 3, Span::new(26, 26)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_greater_tokens() {
+        let input = "> >= >\nd20 >= 15";
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Greater, 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::GreaterEqual, 1, 3, Span::new(2, 4)),
+            Token::new(TokenType::Greater, 1, 6, Span::new(5, 6)),
+            Token::new(TokenType::Die(KeywordForm::Short), 2, 1, Span::new(7, 8)),
+            Token::new(TokenType::Int(20), 2, 2, Span::new(8, 10)),
+            Token::new(TokenType::GreaterEqual, 2, 5, Span::new(11, 13)),
+            Token::new(TokenType::Int(15), 2, 8, Span::new(14, 16)),
+            Token::new(TokenType::Eof, 2, 10, Span::new(16, 16)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_equal_tokens() {
+        let input = "== = ===";
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::EqualEqual, 1, 1, Span::new(0, 2)),
+            Token::new(TokenType::Equal, 1, 4, Span::new(3, 4)),
+            Token::new(TokenType::EqualEqual, 1, 6, Span::new(5, 7)),
+            Token::new(TokenType::Equal, 1, 8, Span::new(7, 8)),
+            Token::new(TokenType::Eof, 1, 9, Span::new(8, 8)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_bang_tokens() {
+        let input = "!= ! !==";
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::BangEqual, 1, 1, Span::new(0, 2)),
+            Token::new(TokenType::Bang, 1, 4, Span::new(3, 4)),
+            Token::new(TokenType::BangEqual, 1, 6, Span::new(5, 7)),
+            Token::new(TokenType::Equal, 1, 8, Span::new(7, 8)),
+            Token::new(TokenType::Eof, 1, 9, Span::new(8, 8)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
 
-        while input.peek().is_some() {
-            let token = read_token(&mut input, &mut current_line, &mut current_column).unwrap();
-            actual_tokens.push(token);
+    #[test]
+    fn test_bang_equal_is_matched_once() {
+        let input = "!!=\nd6 != 3";
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Bang, 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::BangEqual, 1, 2, Span::new(1, 3)),
+            Token::new(TokenType::Die(KeywordForm::Short), 2, 1, Span::new(4, 5)),
+            Token::new(TokenType::Int(6), 2, 2, Span::new(5, 6)),
+            Token::new(TokenType::BangEqual, 2, 4, Span::new(7, 9)),
+            Token::new(TokenType::Int(3), 2, 7, Span::new(10, 11)),
+            Token::new(TokenType::Eof, 2, 8, Span::new(11, 11)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_lexer() {
+        let lexer = Lexer::new("2d6\n+ 3");
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(2), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 2, Span::new(1, 2)),
+            Token::new(TokenType::Int(6), 1, 3, Span::new(2, 3)),
+            Token::new(TokenType::Plus, 2, 1, Span::new(4, 5)),
+            Token::new(TokenType::Int(3), 2, 3, Span::new(6, 7)),
+            Token::new(TokenType::Eof, 2, 4, Span::new(7, 7)),
+        ];
+
+        let actual_tokens = lexer.collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_lexer_repeats_eof() {
+        let mut lexer = Lexer::new("1");
+
+        assert_eq!(
+            lexer.next_token().unwrap(),
+            Token::new(TokenType::Int(1), 1, 1, Span::new(0, 1))
+        );
+
+        for _ in 0..3 {
+            assert_eq!(
+                lexer.next_token().unwrap(),
+                Token::new(TokenType::Eof, 1, 2, Span::new(1, 1))
+            );
         }
+    }
+
+    #[test]
+    fn test_tabs_and_carriage_returns() {
+        let input = "(\t)\r\n+";
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::LeftParen, 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::RightParen, 1, 3, Span::new(2, 3)),
+            Token::new(TokenType::Plus, 2, 1, Span::new(5, 6)),
+            Token::new(TokenType::Eof, 2, 2, Span::new(6, 6)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
 
         assert_eq!(expected_tokens, actual_tokens);
     }
+
+    #[test]
+    fn test_lexer_iterator_stops_after_eof() {
+        let mut lexer = Lexer::new("+");
+
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::new(TokenType::Plus, 1, 1, Span::new(0, 1))
+        );
+        assert_eq!(
+            lexer.next().unwrap().unwrap(),
+            Token::new(TokenType::Eof, 1, 2, Span::new(1, 1))
+        );
+        assert!(lexer.next().is_none());
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn test_lexer_iterator_yields_errors() {
+        let results: Vec<_> = Lexer::new("1 @ 2").collect();
+
+        assert_eq!(results.len(), 4);
+        assert!(matches!(
+            results[1],
+            Err(TokenError::UnsupportedToken { ref literal, .. }) if literal == "@"
+        ));
+        assert_eq!(results[3].as_ref().unwrap().token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_line_comments() {
+        let input = "2d6 + 3 # sneak attack\n# comment only\n1 # no trailing newline";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(2), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 2, Span::new(1, 2)),
+            Token::new(TokenType::Int(6), 1, 3, Span::new(2, 3)),
+            Token::new(TokenType::Plus, 1, 5, Span::new(4, 5)),
+            Token::new(TokenType::Int(3), 1, 7, Span::new(6, 7)),
+            Token::new(TokenType::Int(1), 3, 1, Span::new(38, 39)),
+            Token::new(TokenType::Eof, 3, 24, Span::new(61, 61)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_tab_indentation_and_crlf() {
+        let input = "\t2d6\r\n\t\t+ 1\r\n";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(2), 1, 2, Span::new(1, 2)),
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 3, Span::new(2, 3)),
+            Token::new(TokenType::Int(6), 1, 4, Span::new(3, 4)),
+            Token::new(TokenType::Plus, 2, 3, Span::new(8, 9)),
+            Token::new(TokenType::Int(1), 2, 5, Span::new(10, 11)),
+            Token::new(TokenType::Eof, 3, 1, Span::new(13, 13)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_integer_out_of_range() {
+        let mut lexer = Lexer::new("2147483647 2147483648");
+
+        assert_eq!(
+            lexer.next_token().unwrap(),
+            Token::new(TokenType::Int(i32::MAX), 1, 1, Span::new(0, 10))
+        );
+        assert!(matches!(
+            lexer.next_token(),
+            Err(TokenError::NumberOutOfRange { ref literal, line: 1, column: 12 }) if literal == "2147483648"
+        ));
+    }
+
+    #[test]
+    fn test_floats() {
+        let input = "2.75 10 3. .5";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Float(2.75), 1, 1, Span::new(0, 4)),
+            Token::new(TokenType::Int(10), 1, 6, Span::new(5, 7)),
+            Token::new(TokenType::Int(3), 1, 9, Span::new(8, 9)),
+            Token::new(TokenType::Dot, 1, 10, Span::new(9, 10)),
+            Token::new(TokenType::Dot, 1, 12, Span::new(11, 12)),
+            Token::new(TokenType::Int(5), 1, 13, Span::new(12, 13)),
+            Token::new(TokenType::Eof, 1, 14, Span::new(13, 13)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_slash_comments() {
+        let input = "d20 / 2 // halved\n  + 1 //";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Int(20), 1, 2, Span::new(1, 3)),
+            Token::new(TokenType::Slash, 1, 5, Span::new(4, 5)),
+            Token::new(TokenType::Int(2), 1, 7, Span::new(6, 7)),
+            Token::new(TokenType::Plus, 2, 3, Span::new(20, 21)),
+            Token::new(TokenType::Int(1), 2, 5, Span::new(22, 23)),
+            Token::new(TokenType::Eof, 2, 9, Span::new(26, 26)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_percentile_dice() {
+        let input = "d% 2d% die% % d %";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::PercentileDie, 1, 1, Span::new(0, 2)),
+            Token::new(TokenType::Int(2), 1, 4, Span::new(3, 4)),
+            Token::new(TokenType::PercentileDie, 1, 5, Span::new(4, 6)),
+            Token::new(TokenType::PercentileDie, 1, 8, Span::new(7, 11)),
+            Token::new(TokenType::Percent, 1, 13, Span::new(12, 13)),
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 15, Span::new(14, 15)),
+            Token::new(TokenType::Percent, 1, 17, Span::new(16, 17)),
+            Token::new(TokenType::Eof, 1, 18, Span::new(17, 17)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let input =
+            "1 /* one line */ 2\n/* spans\ntwo lines */ 3\n/* outer /* inner */ still outer */ 4";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(1), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Int(2), 1, 18, Span::new(17, 18)),
+            Token::new(TokenType::Int(3), 3, 14, Span::new(41, 42)),
+            Token::new(TokenType::Int(4), 4, 37, Span::new(79, 80)),
+            Token::new(TokenType::Eof, 4, 38, Span::new(80, 80)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let mut lexer = Lexer::new("1 /* /* */");
+
+        assert_eq!(
+            lexer.next_token().unwrap(),
+            Token::new(TokenType::Int(1), 1, 1, Span::new(0, 1))
+        );
+        assert!(matches!(
+            lexer.next_token(),
+            Err(TokenError::UnterminatedBlockComment { line: 1, column: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_span_text() {
+        let input = "# café\n4d6 keep 3 <= 12";
+        let tokens = Lexer::new(input).collect::<Result<Vec<_>, _>>().unwrap();
+
+        let texts: Vec<&str> = tokens.iter().map(|token| token.span.text(input)).collect();
+
+        assert_eq!(texts, vec!["4", "d", "6", "keep", "3", "<=", "12", ""]);
+        assert_eq!(tokens[0].span, Span::new(8, 9));
+        assert_eq!(tokens[3].span, Span::new(12, 16));
+    }
+
+    #[test]
+    fn test_fate_dice() {
+        let input = "4dF dF df dFoo daF F";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(4), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::FateDie, 1, 2, Span::new(1, 3)),
+            Token::new(TokenType::FateDie, 1, 5, Span::new(4, 6)),
+            Token::new(TokenType::FateDie, 1, 8, Span::new(7, 9)),
+            Token::new(
+                TokenType::Unrecognized(String::from("dFoo")),
+                1,
+                11,
+                Span::new(10, 14),
+            ),
+            Token::new(
+                TokenType::Unrecognized(String::from("daF")),
+                1,
+                16,
+                Span::new(15, 18),
+            ),
+            Token::new(
+                TokenType::Unrecognized(String::from("F")),
+                1,
+                20,
+                Span::new(19, 20),
+            ),
+            Token::new(TokenType::Eof, 1, 21, Span::new(20, 20)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_column_resets_after_blank_lines() {
+        let input = "\n\n\n+";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Plus, 4, 1, Span::new(3, 4)),
+            Token::new(TokenType::Eof, 4, 2, Span::new(4, 4)),
+        ];
+
+        let actual_tokens = tokenize_strict(input).unwrap();
+
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
+    #[test]
+    fn test_token_type_display() {
+        let cases = vec![
+            (TokenType::Int(42), "42"),
+            (TokenType::Float(2.5), "2.5"),
+            (TokenType::Die(KeywordForm::Long), "die"),
+            (TokenType::Die(KeywordForm::Short), "d"),
+            (TokenType::Keep(KeywordForm::Long), "keep"),
+            (TokenType::Keep(KeywordForm::Short), "k"),
+            (TokenType::Drop, "drop"),
+            (TokenType::Explode, "explode"),
+            (TokenType::Emphasis, "emphasis"),
+            (TokenType::Unrecognized(String::from("foo")), "foo"),
+            (TokenType::LeftParen, "("),
+            (TokenType::RightParen, ")"),
+            (TokenType::LeftBrace, "{"),
+            (TokenType::RightBrace, "}"),
+            (TokenType::Plus, "+"),
+            (TokenType::Minus, "-"),
+            (TokenType::Star, "*"),
+            (TokenType::Slash, "/"),
+            (TokenType::Dot, "."),
+            (TokenType::Bang, "!"),
+            (TokenType::BangEqual, "!="),
+            (TokenType::Eof, "<EOF>"),
+            (TokenType::Less, "<"),
+            (TokenType::LessEqual, "<="),
+            (TokenType::Greater, ">"),
+            (TokenType::GreaterEqual, ">="),
+            (TokenType::EqualEqual, "=="),
+            (TokenType::Equal, "="),
+            (TokenType::PercentileDie, "d%"),
+            (TokenType::Percent, "%"),
+            (TokenType::FateDie, "dF"),
+            (TokenType::Caret, "^"),
+            (TokenType::Reroll(KeywordForm::Long), "reroll"),
+            (TokenType::Reroll(KeywordForm::Short), "rr"),
+            (TokenType::Comma, ","),
+            (TokenType::Min, "min"),
+            (TokenType::Max, "max"),
+            (TokenType::LeftBracket, "["),
+            (TokenType::RightBracket, "]"),
+            (TokenType::Advantage, "advantage"),
+            (TokenType::Disadvantage, "disadvantage"),
+            (TokenType::Colon, ":"),
+            (TokenType::DotDot, ".."),
+            (TokenType::Once, "once"),
+            (TokenType::Until, "until"),
+            (TokenType::If, "if"),
+            (TokenType::Else, "else"),
+            (TokenType::Then, "then"),
+            (TokenType::Let, "let"),
+            (TokenType::In, "in"),
+            (TokenType::True, "true"),
+            (TokenType::False, "false"),
+            (TokenType::Fn, "fn"),
+            (TokenType::Return, "return"),
+            (TokenType::Count, "count"),
+            (TokenType::Sum, "sum"),
+            (TokenType::Sort, "sort"),
+            (TokenType::KeepHighest, "kh"),
+            (TokenType::KeepLowest, "kl"),
+            (TokenType::DropHighest, "dh"),
+            (TokenType::DropLowest, "dl"),
+            (TokenType::On, "on"),
+            (TokenType::High, "high"),
+            (TokenType::Low, "low"),
+            (TokenType::Semicolon, ";"),
+        ];
+
+        for (token_type, expected) in cases {
+            assert_eq!(token_type.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_lexer_collects_until_eof() {
+        let tokens: Vec<Token> = Lexer::new("( )").map(Result::unwrap).collect();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::LeftParen, 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::RightParen, 1, 3, Span::new(2, 3)),
+            Token::new(TokenType::Eof, 1, 4, Span::new(3, 3)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+    }
+
+    #[test]
+    fn test_token_display() {
+        let token = Token::new(TokenType::Plus, 1, 5, Span::new(4, 5));
+        assert_eq!(token.to_string(), "[1:5] +");
+
+        let token = Token::new(TokenType::Eof, 3, 1, Span::new(10, 10));
+        assert_eq!(token.to_string(), "[3:1] <EOF>");
+    }
+
+    #[test]
+    fn test_token_error_display() {
+        let error = TokenError::UnsupportedToken {
+            literal: String::from("@"),
+            line: 1,
+            column: 1,
+        };
+        assert_eq!(error.to_string(), "unsupported character '@'");
+
+        let error = TokenError::InvalidNumberToken {
+            literal: String::from("12x"),
+            line: 1,
+            column: 1,
+        };
+        assert_eq!(error.to_string(), "invalid number literal '12x'");
+
+        let error = TokenError::NumberOutOfRange {
+            literal: String::from("2147483648"),
+            line: 1,
+            column: 1,
+        };
+        assert_eq!(
+            error.to_string(),
+            "number literal '2147483648' is out of range"
+        );
+
+        let error = TokenError::UnterminatedBlockComment { line: 1, column: 1 };
+        assert_eq!(error.to_string(), "unterminated block comment");
+    }
+
+    #[test]
+    fn test_tokenize_strict_stops_at_first_error() {
+        assert!(matches!(
+            tokenize_strict("2d6 + @ + $"),
+            Err(TokenError::UnsupportedToken { ref literal, .. }) if literal == "@"
+        ));
+    }
+
+    #[test]
+    fn test_token_error_is_std_error() {
+        fn lex(input: &str) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+            Ok(tokenize_strict(input)?)
+        }
+
+        let error = lex("1 + @").unwrap_err();
+        assert_eq!(error.to_string(), "unsupported character '@'");
+    }
+
+    #[test]
+    fn test_end_column() {
+        let tokens = tokenize_strict("emphasis 1234\n  <=").unwrap();
+
+        let columns: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.column, token.end_column()))
+            .collect();
+
+        assert_eq!(columns, vec![(1, 9), (10, 14), (3, 5), (5, 5)]);
+    }
+
+    #[test]
+    fn test_token_classification() {
+        let tokens = tokenize_strict(
+            "+ - * / ! != < <= > >= == 7 1.5 true false d keep drop explode emphasis dF d% foo (",
+        )
+        .unwrap();
+
+        let operators = tokens.iter().filter(|token| token.is_operator()).count();
+        let literals = tokens.iter().filter(|token| token.is_literal()).count();
+        let keywords = tokens.iter().filter(|token| token.is_keyword()).count();
+
+        assert_eq!(operators, 11);
+        assert_eq!(literals, 4);
+        assert_eq!(keywords, 7);
+
+        let foo = &tokens[22];
+        assert!(!foo.is_operator() && !foo.is_literal() && !foo.is_keyword());
+    }
+
+    #[test]
+    fn test_as_int() {
+        let tokens = tokenize_strict("42 4.2 d").unwrap();
+
+        assert_eq!(tokens[0].as_int(), Some(42));
+        assert_eq!(tokens[1].as_int(), None);
+        assert_eq!(tokens[2].as_int(), None);
+    }
+
+    #[test]
+    fn test_equal_tokens_hash_equally() {
+        use std::collections::{hash_map::DefaultHasher, HashSet};
+
+        fn hash_of(token: &Token) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            token.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let first = tokenize_strict("4d6 k 3 2.5 foo").unwrap();
+        let second = tokenize_strict("4d6 k 3 2.5 foo").unwrap();
+
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a, b);
+            assert_eq!(hash_of(a), hash_of(b));
+        }
+
+        let unique: HashSet<TokenType> = tokenize_strict("1 1 2 d d die foo foo 2.5 2.5")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(unique.len(), 7);
+    }
+
+    #[test]
+    fn test_float_equality_agrees_with_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash_of(token_type: &TokenType) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            token_type.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let (zero, negative_zero) = (TokenType::Float(0.0), TokenType::Float(-0.0));
+        let nan = TokenType::Float(f64::NAN);
+
+        assert_ne!(zero, negative_zero);
+        assert_eq!(nan, nan.clone());
+        assert_eq!(hash_of(&nan), hash_of(&nan.clone()));
+    }
+
+    #[test]
+    fn test_tokens_sort_by_position() {
+        let tokens = tokenize_strict("4d6 k 3\n+ 2 >= 10\n(1)").unwrap();
+
+        let mut shuffled = tokens.clone();
+        shuffled.reverse();
+        shuffled.swap(0, 5);
+        shuffled.swap(2, 9);
+        shuffled.sort();
+
+        assert_eq!(shuffled, tokens);
+    }
+
+    #[test]
+    fn test_token_order_tiebreaks_on_type() {
+        let plus = Token::new(TokenType::Plus, 1, 1, Span::new(0, 1));
+        let minus = Token::new(TokenType::Minus, 1, 1, Span::new(0, 1));
+        let small = Token::new(TokenType::Int(1), 1, 1, Span::new(0, 1));
+        let big = Token::new(TokenType::Int(9), 1, 1, Span::new(0, 1));
+
+        assert!(plus < minus);
+        assert!(small < big);
+        assert!(big < plus);
+        assert_eq!(plus.cmp(&plus.clone()), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_float_order_agrees_with_equality() {
+        let floats = [0.0, -0.0, 1.5, f64::NAN].map(TokenType::Float);
+
+        for a in &floats {
+            for b in &floats {
+                assert_eq!(a == b, a.cmp(b) == Ordering::Equal, "{a:?} and {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_position() {
+        let error = tokenize_strict("d6\n@").unwrap_err();
+
+        assert_eq!((error.line(), error.column()), (2, 1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let tokens = tokenize_strict("4d6 keep 3 + 1.5 foo").unwrap();
+
+        let json = serde_json::to_string(&tokens).unwrap();
+        let round_tripped: Vec<Token> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(tokens, round_tripped);
+    }
+
+    #[test]
+    fn test_columns_count_unicode_scalars() {
+        let mut lexer = Lexer::new("é+");
+
+        assert!(matches!(
+            lexer.next_token(),
+            Err(TokenError::UnsupportedToken { ref literal, line: 1, column: 1 }) if literal == "é"
+        ));
+        assert_eq!(
+            lexer.next_token().unwrap(),
+            Token::new(TokenType::Plus, 1, 2, Span::new(2, 3))
+        );
+    }
+
+    #[test]
+    fn test_lexing_resumes_after_unicode_error() {
+        let results: Vec<_> = Lexer::new("d6 é d8").collect();
+
+        assert!(matches!(
+            results[2],
+            Err(TokenError::UnsupportedToken {
+                line: 1,
+                column: 4,
+                ..
+            })
+        ));
+
+        let d8: Vec<&Token> = results[3..5].iter().map(|r| r.as_ref().unwrap()).collect();
+        assert_eq!(
+            d8,
+            vec![
+                &Token::new(TokenType::Die(KeywordForm::Short), 1, 6, Span::new(6, 7)),
+                &Token::new(TokenType::Int(8), 1, 7, Span::new(7, 8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_matches_manual_loop() {
+        let input = "4d6 keep 3\n+ d% >= 10";
+        let mut chars = input.chars().peekable();
+
+        let mut expected = vec![];
+
+        let mut current_line = 1;
+        let mut current_column = 1;
+        let mut current_offset = 0;
+
+        loop {
+            let token = read_token(
+                &mut chars,
+                &mut current_line,
+                &mut current_column,
+                &mut current_offset,
+            )
+            .unwrap();
+            let is_eof = token.token_type == TokenType::Eof;
+
+            expected.push(token);
+
+            if is_eof {
+                break;
+            }
+        }
+
+        let actual: Vec<Token> = tokenize(input).into_iter().map(Result::unwrap).collect();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_tokenize_continues_past_errors() {
+        let results = tokenize("1 @ 2 $");
+
+        assert_eq!(results.len(), 5);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap().token_type, TokenType::Int(2));
+        assert!(results[3].is_err());
+        assert_eq!(results[4].as_ref().unwrap().token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_hex_integers() {
+        let tokens: Vec<TokenType> = tokenize_strict("0xFF 0x0 0X1a 0d6")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Int(255),
+                TokenType::Int(0),
+                TokenType::Int(26),
+                TokenType::Int(0),
+                TokenType::Die(KeywordForm::Short),
+                TokenType::Int(6),
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_hex_integers() {
+        assert!(matches!(
+            tokenize_strict("0xZ"),
+            Err(TokenError::InvalidNumberToken { ref literal, .. }) if literal == "0x"
+        ));
+        assert!(matches!(
+            tokenize_strict("0x100000000"),
+            Err(TokenError::NumberOutOfRange { ref literal, .. }) if literal == "0x100000000"
+        ));
+    }
+
+    #[test]
+    fn test_percent_in_expressions() {
+        let tokens: Vec<TokenType> = tokenize_strict("2d% + 10 % 3")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Int(2),
+                TokenType::PercentileDie,
+                TokenType::Plus,
+                TokenType::Int(10),
+                TokenType::Percent,
+                TokenType::Int(3),
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let tokens = tokenize_strict("1_000 1_000_000 2_5.5").unwrap();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(1000), 1, 1, Span::new(0, 5)),
+            Token::new(TokenType::Int(1_000_000), 1, 7, Span::new(6, 15)),
+            Token::new(TokenType::Float(25.5), 1, 17, Span::new(16, 21)),
+            Token::new(TokenType::Eof, 1, 22, Span::new(21, 21)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+    }
+
+    #[test]
+    fn test_malformed_digit_separators() {
+        for input in ["1__0", "1_", "_1"] {
+            assert!(matches!(
+                tokenize_strict(input),
+                Err(TokenError::InvalidNumberToken { ref literal, line: 1, column: 1 }) if literal == input
+            ));
+        }
+
+        assert!(matches!(
+            tokenize_strict("2_147_483_648"),
+            Err(TokenError::NumberOutOfRange { ref literal, .. }) if literal == "2_147_483_648"
+        ));
+    }
+
+    #[test]
+    fn test_caret() {
+        let tokens = tokenize_strict("2^4 dX^2").unwrap();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(2), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Caret, 1, 2, Span::new(1, 2)),
+            Token::new(TokenType::Int(4), 1, 3, Span::new(2, 3)),
+            Token::new(
+                TokenType::Unrecognized(String::from("dX")),
+                1,
+                5,
+                Span::new(4, 6),
+            ),
+            Token::new(TokenType::Caret, 1, 7, Span::new(6, 7)),
+            Token::new(TokenType::Int(2), 1, 8, Span::new(7, 8)),
+            Token::new(TokenType::Eof, 1, 9, Span::new(8, 8)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+        assert!(tokens[1].is_operator());
+    }
+
+    #[test]
+    fn test_reroll() {
+        let tokens: Vec<TokenType> = tokenize_strict("2d6 reroll 1 rr rerolls")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Int(2),
+                TokenType::Die(KeywordForm::Short),
+                TokenType::Int(6),
+                TokenType::Reroll(KeywordForm::Long),
+                TokenType::Int(1),
+                TokenType::Reroll(KeywordForm::Short),
+                TokenType::Unrecognized(String::from("rerolls")),
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comma() {
+        let tokens = tokenize_strict("f(a, b)").unwrap();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(
+                TokenType::Unrecognized(String::from("f")),
+                1,
+                1,
+                Span::new(0, 1),
+            ),
+            Token::new(TokenType::LeftParen, 1, 2, Span::new(1, 2)),
+            Token::new(
+                TokenType::Unrecognized(String::from("a")),
+                1,
+                3,
+                Span::new(2, 3),
+            ),
+            Token::new(TokenType::Comma, 1, 4, Span::new(3, 4)),
+            Token::new(
+                TokenType::Unrecognized(String::from("b")),
+                1,
+                6,
+                Span::new(5, 6),
+            ),
+            Token::new(TokenType::RightParen, 1, 7, Span::new(6, 7)),
+            Token::new(TokenType::Eof, 1, 8, Span::new(7, 7)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+        assert!(tokens[3].is_separator());
+        assert!(!tokens[3].is_operator());
+    }
+
+    #[test]
+    fn test_min_and_max() {
+        let tokens: Vec<TokenType> = tokenize_strict("max(d20, 10) 2d6 min 4 minimum maxx")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Max,
+                TokenType::LeftParen,
+                TokenType::Die(KeywordForm::Short),
+                TokenType::Int(20),
+                TokenType::Comma,
+                TokenType::Int(10),
+                TokenType::RightParen,
+                TokenType::Int(2),
+                TokenType::Die(KeywordForm::Short),
+                TokenType::Int(6),
+                TokenType::Min,
+                TokenType::Int(4),
+                TokenType::Unrecognized(String::from("minimum")),
+                TokenType::Unrecognized(String::from("maxx")),
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_brackets() {
+        let tokens = tokenize_strict("[1..6]").unwrap();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::LeftBracket, 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Int(1), 1, 2, Span::new(1, 2)),
+            Token::new(TokenType::DotDot, 1, 3, Span::new(2, 4)),
+            Token::new(TokenType::Int(6), 1, 5, Span::new(4, 5)),
+            Token::new(TokenType::RightBracket, 1, 6, Span::new(5, 6)),
+            Token::new(TokenType::Eof, 1, 7, Span::new(6, 6)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+    }
+
+    #[test]
+    fn test_advantage_and_disadvantage() {
+        let tokens: Vec<TokenType> =
+            tokenize_strict("advantage adv disadvantage dis advantages disadvantaged")
+                .unwrap()
+                .into_iter()
+                .map(|token| token.token_type)
+                .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Advantage,
+                TokenType::Unrecognized(String::from("adv")),
+                TokenType::Disadvantage,
+                TokenType::Unrecognized(String::from("dis")),
+                TokenType::Unrecognized(String::from("advantages")),
+                TokenType::Unrecognized(String::from("disadvantaged")),
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_colon() {
+        let tokens = tokenize_strict("1:2 a:b").unwrap();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(1), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Colon, 1, 2, Span::new(1, 2)),
+            Token::new(TokenType::Int(2), 1, 3, Span::new(2, 3)),
+            Token::new(
+                TokenType::Unrecognized(String::from("a")),
+                1,
+                5,
+                Span::new(4, 5),
+            ),
+            Token::new(TokenType::Colon, 1, 6, Span::new(5, 6)),
+            Token::new(
+                TokenType::Unrecognized(String::from("b")),
+                1,
+                7,
+                Span::new(6, 7),
+            ),
+            Token::new(TokenType::Eof, 1, 8, Span::new(7, 7)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+        assert!(tokens[1].is_separator());
+    }
+
+    #[test]
+    fn test_keywords_are_case_insensitive() {
+        let tokens: Vec<TokenType> = tokenize_strict("2D6 KEEP Drop FooBar 4DF")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                TokenType::Int(2),
+                TokenType::Die(KeywordForm::Short),
+                TokenType::Int(6),
+                TokenType::Keep(KeywordForm::Long),
+                TokenType::Drop,
+                TokenType::Unrecognized(String::from("FooBar")),
+                TokenType::Int(4),
+                TokenType::FateDie,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dot_dot() {
+        let tokens = tokenize_strict("1..6 1.6 1...6").unwrap();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Int(1), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::DotDot, 1, 2, Span::new(1, 3)),
+            Token::new(TokenType::Int(6), 1, 4, Span::new(3, 4)),
+            Token::new(TokenType::Float(1.6), 1, 6, Span::new(5, 8)),
+            Token::new(TokenType::Int(1), 1, 10, Span::new(9, 10)),
+            Token::new(TokenType::DotDot, 1, 11, Span::new(10, 12)),
+            Token::new(TokenType::Dot, 1, 13, Span::new(12, 13)),
+            Token::new(TokenType::Int(6), 1, 14, Span::new(13, 14)),
+            Token::new(TokenType::Eof, 1, 15, Span::new(14, 14)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+    }
+
+    #[test]
+    fn test_identifiers_with_digits() {
+        let token_types: Vec<TokenType> = tokenize_strict("roll1 d6x2 _foo max1 sum2 rr1")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::Unrecognized(String::from("roll1")),
+            TokenType::Die(KeywordForm::Short),
+            TokenType::Int(6),
+            TokenType::Unrecognized(String::from("x2")),
+            TokenType::Unrecognized(String::from("_foo")),
+            TokenType::Unrecognized(String::from("max1")),
+            TokenType::Unrecognized(String::from("sum2")),
+            TokenType::Reroll(KeywordForm::Short),
+            TokenType::Int(1),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_identifiers_may_start_with_underscores() {
+        let token_types: Vec<TokenType> = tokenize_strict("_private __double")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::Unrecognized(String::from("_private")),
+            TokenType::Unrecognized(String::from("__double")),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_reroll_strategy_keywords() {
+        let input = "reroll once 1\nreroll until > 2";
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Reroll(KeywordForm::Long), 1, 1, Span::new(0, 6)),
+            Token::new(TokenType::Once, 1, 8, Span::new(7, 11)),
+            Token::new(TokenType::Int(1), 1, 13, Span::new(12, 13)),
+            Token::new(
+                TokenType::Reroll(KeywordForm::Long),
+                2,
+                1,
+                Span::new(14, 20),
+            ),
+            Token::new(TokenType::Until, 2, 8, Span::new(21, 26)),
+            Token::new(TokenType::Greater, 2, 14, Span::new(27, 28)),
+            Token::new(TokenType::Int(2), 2, 16, Span::new(29, 30)),
+            Token::new(TokenType::Eof, 2, 17, Span::new(30, 30)),
+        ];
+
+        assert_eq!(expected_tokens, tokenize_strict(input).unwrap());
+    }
+
+    #[test]
+    fn test_conditional_keywords() {
+        let token_types: Vec<TokenType> = tokenize_strict("if then else iffy elsewhere")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::If,
+            TokenType::Then,
+            TokenType::Else,
+            TokenType::Unrecognized(String::from("iffy")),
+            TokenType::Unrecognized(String::from("elsewhere")),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_let_binding_keywords() {
+        let token_types: Vec<TokenType> = tokenize_strict("let roll = 3d6 in roll + 2")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::Let,
+            TokenType::Unrecognized(String::from("roll")),
+            TokenType::Equal,
+            TokenType::Int(3),
+            TokenType::Die(KeywordForm::Short),
+            TokenType::Int(6),
+            TokenType::In,
+            TokenType::Unrecognized(String::from("roll")),
+            TokenType::Plus,
+            TokenType::Int(2),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_boolean_keywords() {
+        let token_types: Vec<TokenType> = tokenize_strict("true false truefalse trueish")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::True,
+            TokenType::False,
+            TokenType::Unrecognized(String::from("truefalse")),
+            TokenType::Unrecognized(String::from("trueish")),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_function_keywords() {
+        let token_types: Vec<TokenType> = tokenize_strict("fn return fnord")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::Fn,
+            TokenType::Return,
+            TokenType::Unrecognized(String::from("fnord")),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_aggregation_keywords() {
+        let token_types: Vec<TokenType> =
+            tokenize_strict("count sum sort min max COUNT Sum SoRt MIN Max minimum")
+                .unwrap()
+                .into_iter()
+                .map(|token| token.token_type)
+                .collect();
+
+        let expected_token_types = vec![
+            TokenType::Count,
+            TokenType::Sum,
+            TokenType::Sort,
+            TokenType::Min,
+            TokenType::Max,
+            TokenType::Count,
+            TokenType::Sum,
+            TokenType::Sort,
+            TokenType::Min,
+            TokenType::Max,
+            TokenType::Unrecognized(String::from("minimum")),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_pool_modifier_keywords() {
+        let token_types: Vec<TokenType> = tokenize_strict("4d6kh3 kl dh2 2d20dl1")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::Int(4),
+            TokenType::Die(KeywordForm::Short),
+            TokenType::Int(6),
+            TokenType::KeepHighest,
+            TokenType::Int(3),
+            TokenType::KeepLowest,
+            TokenType::DropHighest,
+            TokenType::Int(2),
+            TokenType::Int(2),
+            TokenType::Die(KeywordForm::Short),
+            TokenType::Int(20),
+            TokenType::DropLowest,
+            TokenType::Int(1),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_keep_high_keywords() {
+        let token_types: Vec<TokenType> = tokenize_strict("keep high 3 drop LOW 1")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        let expected_token_types = vec![
+            TokenType::Keep(KeywordForm::Long),
+            TokenType::High,
+            TokenType::Int(3),
+            TokenType::Drop,
+            TokenType::Low,
+            TokenType::Int(1),
+            TokenType::Eof,
+        ];
+
+        assert_eq!(expected_token_types, token_types);
+    }
+
+    #[test]
+    fn test_semicolon() {
+        let tokens = tokenize_strict("d6;2").unwrap();
+
+        let expected_tokens: Vec<Token> = vec![
+            Token::new(TokenType::Die(KeywordForm::Short), 1, 1, Span::new(0, 1)),
+            Token::new(TokenType::Int(6), 1, 2, Span::new(1, 2)),
+            Token::new(TokenType::Semicolon, 1, 3, Span::new(2, 3)),
+            Token::new(TokenType::Int(2), 1, 4, Span::new(3, 4)),
+            Token::new(TokenType::Eof, 1, 5, Span::new(4, 4)),
+        ];
+
+        assert_eq!(expected_tokens, tokens);
+        assert!(tokens[2].is_separator());
+    }
 }
@@ -0,0 +1,305 @@
+use crate::ast::{BinaryOp, Condition, Expr, KeepDropKind, PoolModifier, RerollStrategy, UnaryOp};
+
+/// A computation over an [`Expr`] with one method per kind of node, so that
+/// each pass over the tree doesn't have to match on every variant itself.
+///
+/// The methods receive a node's fields rather than the node, and it's up to
+/// each one to [`accept`](Expr::accept) the visitor on any children it cares
+/// about.
+pub trait ExprVisitor {
+    type Output;
+
+    fn visit_int(&mut self, n: i32) -> Self::Output;
+    fn visit_dice(&mut self, count: &Expr, sides: &Expr) -> Self::Output;
+    fn visit_dice_pool(
+        &mut self,
+        count: &Expr,
+        sides: &Expr,
+        modifier: &PoolModifier,
+    ) -> Self::Output;
+    fn visit_binary_op(&mut self, left: &Expr, op: BinaryOp, right: &Expr) -> Self::Output;
+    fn visit_unary_op(&mut self, op: UnaryOp, operand: &Expr) -> Self::Output;
+    fn visit_comparison(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> Self::Output;
+    fn visit_keep(&mut self, pool: &Expr, count: &Expr) -> Self::Output;
+    fn visit_drop(&mut self, pool: &Expr, count: &Expr) -> Self::Output;
+    fn visit_keep_drop(&mut self, inner: &Expr, kind: KeepDropKind, count: &Expr) -> Self::Output;
+    fn visit_explode(
+        &mut self,
+        inner: &Expr,
+        threshold: Option<&Condition>,
+        depth: u32,
+    ) -> Self::Output;
+    fn visit_reroll(
+        &mut self,
+        inner: &Expr,
+        condition: &Condition,
+        strategy: RerollStrategy,
+    ) -> Self::Output;
+    fn visit_max(&mut self, args: &[Expr]) -> Self::Output;
+    fn visit_min(&mut self, args: &[Expr]) -> Self::Output;
+    fn visit_group(&mut self, inner: &Expr) -> Self::Output;
+}
+
+impl Expr {
+    /// Calls the method of `visitor` for this kind of node.
+    pub fn accept<V: ExprVisitor>(&self, visitor: &mut V) -> V::Output {
+        match self {
+            Expr::Int(n) => visitor.visit_int(*n),
+            Expr::Dice { count, sides } => visitor.visit_dice(count, sides),
+            Expr::DicePool {
+                count,
+                sides,
+                modifier,
+            } => visitor.visit_dice_pool(count, sides, modifier),
+            Expr::BinaryOp { left, op, right } => visitor.visit_binary_op(left, *op, right),
+            Expr::UnaryOp { op, operand } => visitor.visit_unary_op(*op, operand),
+            Expr::Comparison { op, lhs, rhs } => visitor.visit_comparison(*op, lhs, rhs),
+            Expr::Keep { pool, count } => visitor.visit_keep(pool, count),
+            Expr::Drop { pool, count } => visitor.visit_drop(pool, count),
+            Expr::KeepDrop { inner, kind, count } => visitor.visit_keep_drop(inner, *kind, count),
+            Expr::Explode {
+                inner,
+                threshold,
+                depth,
+            } => visitor.visit_explode(inner, threshold.as_ref(), *depth),
+            Expr::Reroll {
+                inner,
+                condition,
+                strategy,
+            } => visitor.visit_reroll(inner, condition, *strategy),
+            Expr::Max(args) => visitor.visit_max(args),
+            Expr::Min(args) => visitor.visit_min(args),
+            Expr::Group(inner) => visitor.visit_group(inner),
+        }
+    }
+}
+
+/// Walks the whole tree and rebuilds it unchanged. A starting point for
+/// visitors that only need to change a few kinds of node.
+pub struct DefaultVisitor;
+
+impl DefaultVisitor {
+    fn boxed(&mut self, expr: &Expr) -> Box<Expr> {
+        Box::new(expr.accept(self))
+    }
+
+    fn condition(&mut self, condition: &Condition) -> Condition {
+        Condition {
+            op: condition.op,
+            value: self.boxed(&condition.value),
+        }
+    }
+
+    fn all(&mut self, exprs: &[Expr]) -> Vec<Expr> {
+        exprs.iter().map(|expr| expr.accept(self)).collect()
+    }
+}
+
+impl ExprVisitor for DefaultVisitor {
+    type Output = Expr;
+
+    fn visit_int(&mut self, n: i32) -> Expr {
+        Expr::Int(n)
+    }
+
+    fn visit_dice(&mut self, count: &Expr, sides: &Expr) -> Expr {
+        Expr::Dice {
+            count: self.boxed(count),
+            sides: self.boxed(sides),
+        }
+    }
+
+    fn visit_dice_pool(&mut self, count: &Expr, sides: &Expr, modifier: &PoolModifier) -> Expr {
+        let modifier = match modifier {
+            PoolModifier::KeepHighest(n) => PoolModifier::KeepHighest(self.boxed(n)),
+            PoolModifier::KeepLowest(n) => PoolModifier::KeepLowest(self.boxed(n)),
+            PoolModifier::DropHighest(n) => PoolModifier::DropHighest(self.boxed(n)),
+            PoolModifier::DropLowest(n) => PoolModifier::DropLowest(self.boxed(n)),
+            PoolModifier::None => PoolModifier::None,
+        };
+
+        Expr::DicePool {
+            count: self.boxed(count),
+            sides: self.boxed(sides),
+            modifier,
+        }
+    }
+
+    fn visit_binary_op(&mut self, left: &Expr, op: BinaryOp, right: &Expr) -> Expr {
+        Expr::BinaryOp {
+            left: self.boxed(left),
+            op,
+            right: self.boxed(right),
+        }
+    }
+
+    fn visit_unary_op(&mut self, op: UnaryOp, operand: &Expr) -> Expr {
+        Expr::UnaryOp {
+            op,
+            operand: self.boxed(operand),
+        }
+    }
+
+    fn visit_comparison(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> Expr {
+        Expr::Comparison {
+            op,
+            lhs: self.boxed(lhs),
+            rhs: self.boxed(rhs),
+        }
+    }
+
+    fn visit_keep(&mut self, pool: &Expr, count: &Expr) -> Expr {
+        Expr::Keep {
+            pool: self.boxed(pool),
+            count: self.boxed(count),
+        }
+    }
+
+    fn visit_drop(&mut self, pool: &Expr, count: &Expr) -> Expr {
+        Expr::Drop {
+            pool: self.boxed(pool),
+            count: self.boxed(count),
+        }
+    }
+
+    fn visit_keep_drop(&mut self, inner: &Expr, kind: KeepDropKind, count: &Expr) -> Expr {
+        Expr::KeepDrop {
+            inner: self.boxed(inner),
+            kind,
+            count: self.boxed(count),
+        }
+    }
+
+    fn visit_explode(&mut self, inner: &Expr, threshold: Option<&Condition>, depth: u32) -> Expr {
+        Expr::Explode {
+            inner: self.boxed(inner),
+            threshold: threshold.map(|threshold| self.condition(threshold)),
+            depth,
+        }
+    }
+
+    fn visit_reroll(
+        &mut self,
+        inner: &Expr,
+        condition: &Condition,
+        strategy: RerollStrategy,
+    ) -> Expr {
+        Expr::Reroll {
+            inner: self.boxed(inner),
+            condition: self.condition(condition),
+            strategy,
+        }
+    }
+
+    fn visit_max(&mut self, args: &[Expr]) -> Expr {
+        Expr::Max(self.all(args))
+    }
+
+    fn visit_min(&mut self, args: &[Expr]) -> Expr {
+        Expr::Min(self.all(args))
+    }
+
+    fn visit_group(&mut self, inner: &Expr) -> Expr {
+        Expr::Group(self.boxed(inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, tokenize_strict};
+
+    fn parse_str(input: &str) -> Expr {
+        parse(&tokenize_strict(input).unwrap()).unwrap()
+    }
+
+    /// Counts the dice expressions in a tree, to check that visitors can
+    /// choose which children to descend into.
+    struct DiceCounter;
+
+    impl ExprVisitor for DiceCounter {
+        type Output = usize;
+
+        fn visit_int(&mut self, _: i32) -> usize {
+            0
+        }
+
+        fn visit_dice(&mut self, count: &Expr, sides: &Expr) -> usize {
+            1 + count.accept(self) + sides.accept(self)
+        }
+
+        fn visit_dice_pool(&mut self, count: &Expr, sides: &Expr, _: &PoolModifier) -> usize {
+            1 + count.accept(self) + sides.accept(self)
+        }
+
+        fn visit_binary_op(&mut self, left: &Expr, _: BinaryOp, right: &Expr) -> usize {
+            left.accept(self) + right.accept(self)
+        }
+
+        fn visit_unary_op(&mut self, _: UnaryOp, operand: &Expr) -> usize {
+            operand.accept(self)
+        }
+
+        fn visit_comparison(&mut self, _: BinaryOp, lhs: &Expr, rhs: &Expr) -> usize {
+            lhs.accept(self) + rhs.accept(self)
+        }
+
+        fn visit_keep(&mut self, pool: &Expr, count: &Expr) -> usize {
+            pool.accept(self) + count.accept(self)
+        }
+
+        fn visit_drop(&mut self, pool: &Expr, count: &Expr) -> usize {
+            pool.accept(self) + count.accept(self)
+        }
+
+        fn visit_keep_drop(&mut self, inner: &Expr, _: KeepDropKind, count: &Expr) -> usize {
+            inner.accept(self) + count.accept(self)
+        }
+
+        fn visit_explode(&mut self, inner: &Expr, _: Option<&Condition>, _: u32) -> usize {
+            inner.accept(self)
+        }
+
+        fn visit_reroll(&mut self, inner: &Expr, _: &Condition, _: RerollStrategy) -> usize {
+            inner.accept(self)
+        }
+
+        fn visit_max(&mut self, args: &[Expr]) -> usize {
+            args.iter().map(|arg| arg.accept(self)).sum()
+        }
+
+        fn visit_min(&mut self, args: &[Expr]) -> usize {
+            args.iter().map(|arg| arg.accept(self)).sum()
+        }
+
+        fn visit_group(&mut self, inner: &Expr) -> usize {
+            inner.accept(self)
+        }
+    }
+
+    #[test]
+    fn test_default_visitor_rebuilds_tree() {
+        for input in [
+            "1 + 2 * -3",
+            "(d4)d6 >= 3",
+            "4d6kh3 + 4d6 keep 3 + 4d6 drop 1",
+            "(2d6!) keep low 1",
+            "2d6 explode on >= (1 + 4)",
+            "3d6 reroll until < 2",
+            "max(d6, min(d8, 3))",
+        ] {
+            let expr = parse_str(input);
+
+            assert_eq!(expr, expr.accept(&mut DefaultVisitor));
+        }
+    }
+
+    #[test]
+    fn test_custom_visitor() {
+        assert_eq!(0, parse_str("1 + 2").accept(&mut DiceCounter));
+        assert_eq!(
+            4,
+            parse_str("(d4)d6 + max(d8!, 4d6k3)").accept(&mut DiceCounter)
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+/// Writes `contents` to a file in the temp directory that no other test uses.
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("dice-stats-lang-{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_evaluates_file() {
+    let path = temp_file("ability.ds", "4d6k3\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("mean: 12.24"));
+}
+
+#[test]
+fn test_evaluates_each_line() {
+    let path = temp_file("many.ds", "d6\n\n// Two dice\n2d6 + 1\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        "d6\nmean: 3.50\nmin: 1\nmax: 6\nstd dev: 1.71\nmedian: 3.50\nmode: 1, 2, 3, 4, 5, 6\n\
+         \n\
+         2d6 + 1\nmean: 8.00\nmin: 3\nmax: 13\nstd dev: 2.42\nmedian: 8.00\nmode: 8\n",
+        stdout
+    );
+}
+
+#[test]
+fn test_expressions_span_lines() {
+    let path = temp_file("multiline.ds", "/* a\nb */ d6\n4d6k3\n  + 1\nd4\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with("d6\nmean: 3.50\n"));
+    assert!(stdout.contains("\n\n4d6k3\n  + 1\nmean: 13.24\n"));
+    assert!(stdout.contains("\n\nd4\nmean: 2.50\n"));
+}
+
+#[test]
+fn test_reports_errors_by_file_line() {
+    let path = temp_file("broken.ds", "d6\n2 +\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("[2:3] error: unexpected end of input, expected an expression"));
+}
+
+#[test]
+fn test_reports_division_by_zero() {
+    let path = temp_file("zero.ds", "6 / (d2 - 1)\nd4\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("[1] error: division by zero"));
+    assert!(stdout.contains("d4\nmean: 2.50"));
+}
+
+#[test]
+fn test_missing_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg("does/not/exist.ds")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr.starts_with("error: couldn't read does/not/exist.ds"));
+}
+
+#[test]
+fn test_json_format() {
+    let path = temp_file("json.ds", "d2\n2d6\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .args(["--format", "json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(output.status.success());
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        r#"[{"value":1,"probability":0.5},{"value":2,"probability":0.5}]"#,
+        lines[0]
+    );
+    assert!(lines[1].contains(r#"{"value":7,"probability":0.1666"#));
+}
+
+#[test]
+fn test_unknown_format() {
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .args(["--format", "yaml"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(Some(2), output.status.code());
+    assert!(stderr.starts_with("error: unknown format 'yaml'"));
+}
+
+#[test]
+fn test_conflicting_formats() {
+    for (args, message) in [
+        (
+            &["--format", "json", "--hist"][..],
+            "error: '--hist' conflicts with '--format'",
+        ),
+        (
+            &["--explain", "--hist"],
+            "error: '--hist' conflicts with '--explain'",
+        ),
+        (
+            &["--format", "json", "--format", "text"],
+            "error: '--format' conflicts with '--format'",
+        ),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+            .args(args)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+
+        assert_eq!(Some(2), output.status.code(), "{args:?}");
+        assert!(stderr.starts_with(message), "{args:?}");
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let path = temp_file("hist.ds", "2d6\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg("--hist")
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("mode: 7\n 2   2.78% #"));
+    assert!(stdout.contains(&format!(" 7  16.67% {}\n", "#".repeat(40))));
+}
+
+#[test]
+fn test_explain() {
+    let path = temp_file("explain.ds", "2d6+3\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .arg("--explain")
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with(
+        "2d6+3\n\
+         d6: mean 3.50, range 1..=6\n\
+         2d6: mean 7.00, range 2..=12\n\
+         2d6 + 3: mean 10.00, range 5..=15\n\
+         mean: 10.00\n"
+    ));
+}
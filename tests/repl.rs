@@ -0,0 +1,90 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn run(input: impl AsRef<[u8]>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dice-stats-lang"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_ref())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_prints_summary() {
+    let output = run("2d6\nquit\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("mean: 7.00"));
+    assert!(stdout.contains("min: 2"));
+    assert!(stdout.contains("max: 12"));
+    assert!(stdout.contains("std dev: 2.42"));
+}
+
+#[test]
+fn test_continues_after_errors() {
+    let output = run("2 +\n1 @ 2\nd4\nexit\nd6\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success());
+    assert!(stderr.contains("[1:3] error: unexpected end of input, expected an expression"));
+    assert!(stderr.contains("[1:3] error: unsupported character '@'"));
+    assert!(stdout.contains("mean: 2.50"));
+    assert!(!stdout.contains("mean: 3.50"));
+}
+
+#[test]
+fn test_reports_every_error() {
+    let output = run("1 +; d4; 3 )\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success());
+    assert!(stderr.contains("[1:4] error: unexpected ';'"));
+    assert!(stderr.contains("[1:12] error: expected ';', found ')'"));
+    assert!(!stdout.contains("mean"));
+}
+
+#[test]
+fn test_semicolons_separate_expressions() {
+    let output = run("d4; d6\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains("mean: 2.50"));
+    assert!(stdout.contains("\n\nmean: 3.50"));
+}
+
+#[test]
+fn test_reports_overflow() {
+    let output = run("2147483647 + 1\nd6 * 1000000000\nd4\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(2, stderr.matches("error: arithmetic overflow").count());
+    assert!(stdout.contains("mean: 2.50"));
+}
+
+#[test]
+fn test_reports_unreadable_input() {
+    let output = run(b"d4\n\xff\xfe\nd6\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    assert!(stdout.contains("mean: 2.50"));
+    assert!(stderr.contains("error: couldn't read from stdin"));
+}